@@ -0,0 +1,252 @@
+//! Standalone OpenSearch/Elasticsearch `_bulk` indexer for `CacheEvent`s.
+//!
+//! Buffers incoming events in memory and flushes them as a single `_bulk`
+//! NDJSON request to `<host>/_bulk` whenever the batch reaches
+//! `batch_size` or `flush_interval` elapses, whichever comes first.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tracing::{error, info, warn};
+
+/// Maximum number of bulk flush attempts (the first send plus retries of
+/// individually failed documents, with exponential backoff) before giving
+/// up on them.
+const MAX_FLUSH_RETRIES: u32 = 5;
+
+/// Base delay for flush retry backoff; doubled on each attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CacheEvent {
+    pub url: String,
+    pub method: String,
+    pub status: u16,
+    pub cache_key: String,
+    pub timestamp: u64,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Configuration for a [`CacheEventIndexer`].
+#[derive(Debug, Clone)]
+pub struct CacheEventIndexerConfig {
+    pub host: String,
+    pub index: String,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for CacheEventIndexerConfig {
+    fn default() -> Self {
+        Self {
+            host: "http://localhost:9200".to_string(),
+            index: "http-cache".to_string(),
+            batch_size: 50,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Buffers `CacheEvent`s and flushes them to OpenSearch/Elasticsearch via
+/// the `_bulk` API, either on a background interval (see [`Self::run`]) or
+/// immediately once a batch fills up (see [`Self::enqueue`]).
+pub struct CacheEventIndexer {
+    client: Client,
+    config: CacheEventIndexerConfig,
+    batch: Mutex<Vec<CacheEvent>>,
+    shutdown: Arc<Notify>,
+}
+
+impl CacheEventIndexer {
+    pub fn new(config: CacheEventIndexerConfig) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to create HTTP client"),
+            config,
+            batch: Mutex::new(Vec::new()),
+            shutdown: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Create the index with its mapping (url text+keyword, method/cache_key
+    /// keyword, status short, timestamp date epoch_second) if it doesn't
+    /// already exist.
+    pub async fn ensure_index(&self) -> Result<(), reqwest::Error> {
+        let url = format!("{}/{}", self.config.host, self.config.index);
+        let exists = self.client.head(&url).send().await?;
+        if exists.status().is_success() {
+            return Ok(());
+        }
+
+        let mapping = json!({
+            "mappings": {
+                "properties": {
+                    "url": {
+                        "type": "text",
+                        "fields": {
+                            "keyword": {
+                                "type": "keyword",
+                                "ignore_above": 256
+                            }
+                        }
+                    },
+                    "method": { "type": "keyword" },
+                    "status": { "type": "short" },
+                    "cache_key": { "type": "keyword" },
+                    "timestamp": { "type": "date", "format": "epoch_second" },
+                    "headers": { "type": "object" },
+                    "body": { "type": "text" }
+                }
+            }
+        });
+
+        self.client.put(&url).json(&mapping).send().await?;
+        Ok(())
+    }
+
+    /// Queue `event` for the next flush, flushing immediately if the batch
+    /// just reached `batch_size`.
+    pub async fn enqueue(&self, event: CacheEvent) -> Result<(), reqwest::Error> {
+        let should_flush = {
+            let mut batch = self.batch.lock().await;
+            batch.push(event);
+            batch.len() >= self.config.batch_size
+        };
+
+        if should_flush {
+            self.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Build the `_bulk` NDJSON body: one `{"index": {...}}` action line per
+    /// document followed by the serialized event, joined with `\n` and
+    /// terminated by a trailing `\n`.
+    pub fn build_bulk_body(&self, events: &[CacheEvent]) -> String {
+        let mut lines = Vec::with_capacity(events.len() * 2);
+        for event in events {
+            let action = json!({
+                "index": {
+                    "_index": self.config.index,
+                    "_id": event.cache_key
+                }
+            });
+            lines.push(serde_json::to_string(&action).unwrap());
+            lines.push(serde_json::to_string(event).unwrap());
+        }
+
+        let mut body = lines.join("\n");
+        body.push('\n');
+        body
+    }
+
+    /// Flush whatever is currently buffered. A no-op if the batch is empty.
+    pub async fn flush(&self) -> Result<(), reqwest::Error> {
+        let events = {
+            let mut batch = self.batch.lock().await;
+            if batch.is_empty() {
+                return Ok(());
+            }
+            std::mem::take(&mut *batch)
+        };
+
+        self.send_with_retry(events).await
+    }
+
+    /// POST `events` to `<host>/_bulk`, retrying documents that the bulk
+    /// response reports as individually failed with exponential backoff, up
+    /// to `MAX_FLUSH_RETRIES` attempts before dropping whatever's left.
+    async fn send_with_retry(&self, mut events: Vec<CacheEvent>) -> Result<(), reqwest::Error> {
+        let mut delay = RETRY_BASE_DELAY;
+
+        for attempt in 0..MAX_FLUSH_RETRIES {
+            let body = self.build_bulk_body(&events);
+            let response = self
+                .client
+                .post(format!("{}/_bulk", self.config.host))
+                .header("Content-Type", "application/x-ndjson")
+                .body(body)
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                if attempt + 1 == MAX_FLUSH_RETRIES {
+                    error!("Bulk flush failed after {} attempts: HTTP {}", MAX_FLUSH_RETRIES, response.status());
+                    return Ok(());
+                }
+                warn!("Bulk flush returned {}, retrying ({}/{})", response.status(), attempt + 1, MAX_FLUSH_RETRIES);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                continue;
+            }
+
+            let body: Value = response.json().await?;
+            let failed = Self::failed_events(&body, &events);
+
+            if failed.is_empty() {
+                return Ok(());
+            }
+
+            if attempt + 1 == MAX_FLUSH_RETRIES {
+                warn!("{} document(s) still failing after {} attempts, dropping", failed.len(), MAX_FLUSH_RETRIES);
+                return Ok(());
+            }
+
+            info!("Retrying {} failed document(s) (attempt {}/{})", failed.len(), attempt + 1, MAX_FLUSH_RETRIES);
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            events = failed;
+        }
+
+        Ok(())
+    }
+
+    /// Extract the events whose bulk response item reported an error,
+    /// matched back to their source event by position in the request.
+    fn failed_events(response: &Value, events: &[CacheEvent]) -> Vec<CacheEvent> {
+        let Some(items) = response["items"].as_array() else { return Vec::new() };
+
+        items
+            .iter()
+            .zip(events)
+            .filter_map(|(item, event)| item.get("index")?.get("error").map(|_| event.clone()))
+            .collect()
+    }
+
+    /// Run the background flush loop until shut down: flushes every
+    /// `flush_interval`, and once more on shutdown before returning.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.config.flush_interval);
+        let shutdown = self.shutdown.clone();
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = self.flush().await {
+                        error!("Periodic flush failed: {}", e);
+                    }
+                }
+                _ = shutdown.notified() => {
+                    if let Err(e) = self.flush().await {
+                        error!("Final flush on shutdown failed: {}", e);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Signal the background loop started by [`Self::run`] to flush once
+    /// more and stop.
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+}
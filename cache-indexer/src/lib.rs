@@ -0,0 +1,8 @@
+//! cache-indexer library crate
+//!
+//! Exposes the standalone `_bulk` indexing subsystem so it can be exercised
+//! by integration tests and reused outside the `cache-indexer` binary.
+
+pub mod cache_event_indexer;
+
+pub use cache_event_indexer::{CacheEvent, CacheEventIndexer, CacheEventIndexerConfig};
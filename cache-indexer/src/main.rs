@@ -1,15 +1,60 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use opensearch::{http::transport::TransportBuilder, BulkParts, OpenSearch};
 use rdkafka::{
     config::ClientConfig,
     consumer::{CommitMode, Consumer, StreamConsumer},
     message::Message,
+    producer::{FutureProducer, FutureRecord},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::Notify;
 use tracing::{error, info, warn};
 
+/// Maximum number of times a bulk-index failure is retried (with
+/// exponential backoff) before the offending events are dead-lettered.
+const MAX_INDEX_RETRIES: u32 = 5;
+
+/// Base delay for bulk-index retry backoff; doubled on each attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// How often background maintenance (old-index retention) is checked.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How often a new rolling index is cut for `CacheEvent`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RolloverGranularity {
+    Daily,
+    Hourly,
+}
+
+impl RolloverGranularity {
+    fn parse(value: &str) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "hourly" => Self::Hourly,
+            _ => Self::Daily,
+        }
+    }
+
+    /// Date/time format suffix appended to the base index name, e.g.
+    /// `2024.06.18` (daily) or `2024.06.18.14` (hourly).
+    fn suffix_format(&self) -> &'static str {
+        match self {
+            Self::Daily => "%Y.%m.%d",
+            Self::Hourly => "%Y.%m.%d.%H",
+        }
+    }
+
+    /// The concrete rolling index name for an event's Unix timestamp.
+    fn index_name(&self, base_name: &str, timestamp: u64) -> String {
+        let datetime = DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_else(Utc::now);
+        format!("{}-{}", base_name, datetime.format(self.suffix_format()))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct CacheEvent {
     url: String,
@@ -33,7 +78,18 @@ struct CacheEvent {
 struct Indexer {
     opensearch: OpenSearch,
     consumer: StreamConsumer,
-    index_name: String,
+    producer: FutureProducer,
+    /// Base name shared by the rolling indices (`{base_name}-2024.06.18`)
+    /// and the stable read/write alias dashboards query.
+    base_name: String,
+    rollover: RolloverGranularity,
+    /// How long a rolling index is kept before `prune_old_indices` deletes
+    /// it. `None` disables retention entirely.
+    retention: Option<Duration>,
+    dead_letter_topic: String,
+    /// Rolling index names already confirmed to exist (and aliased), so
+    /// `ensure_rolling_index` doesn't re-check OpenSearch on every event.
+    known_indices: Mutex<HashSet<String>>,
 }
 
 impl Indexer {
@@ -43,6 +99,9 @@ impl Indexer {
         kafka_topic: &str,
         kafka_group: &str,
         index_name: &str,
+        dead_letter_topic: &str,
+        rollover: RolloverGranularity,
+        retention: Option<Duration>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let consumer: StreamConsumer = ClientConfig::new()
             .set("group.id", kafka_group)
@@ -55,6 +114,10 @@ impl Indexer {
         consumer.subscribe(&[kafka_topic])?;
         info!("Subscribed to Kafka topic: {}", kafka_topic);
 
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", kafka_brokers)
+            .create()?;
+
         let transport = TransportBuilder::new(
             opensearch::http::transport::SingleNodeConnectionPool::new(opensearch_url.parse()?),
         )
@@ -65,130 +128,231 @@ impl Indexer {
         Ok(Self {
             opensearch,
             consumer,
-            index_name: index_name.to_string(),
+            producer,
+            base_name: index_name.to_string(),
+            rollover,
+            retention,
+            dead_letter_topic: dead_letter_topic.to_string(),
+            known_indices: Mutex::new(HashSet::new()),
         })
     }
 
-    async fn ensure_index_exists(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let index_body = json!({
-            "mappings": {
-                "properties": {
-                    "url": {
-                        "type": "text",
-                        "fields": {
-                            "keyword": {
-                                "type": "keyword",
-                                "ignore_above": 256
+    fn template_name(&self) -> String {
+        format!("{}-template", self.base_name)
+    }
+
+    /// Upload the mappings/settings as a composable index template so every
+    /// rolling index created from now on (`{base_name}-*`) inherits them
+    /// without needing to repeat the body on each `ensure_rolling_index`
+    /// call.
+    async fn ensure_template(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let template_body = json!({
+            "index_patterns": [format!("{}-*", self.base_name)],
+            "template": {
+                "mappings": {
+                    "properties": {
+                        "url": {
+                            "type": "text",
+                            "fields": {
+                                "keyword": {
+                                    "type": "keyword",
+                                    "ignore_above": 256
+                                }
                             }
-                        }
-                    },
-                    "method": { "type": "keyword" },
-                    "status": { "type": "short" },
-                    "cache_key": { "type": "keyword" },
-                    "timestamp": { "type": "date", "format": "epoch_second" },
-                    "headers": { "type": "object" },
-                    "body": { "type": "text" },
-                    // New fields for user analytics
-                    "user_id": { "type": "keyword" },
-                    "username": { "type": "keyword" },
-                    "client_ip": { "type": "ip" },
-                    "domain": { "type": "keyword" },
-                    "response_size": { "type": "long" },
-                    "request_duration_ms": { "type": "long" },
-                    "content_type": { "type": "keyword" },
-                    "user_agent": {
-                        "type": "text",
-                        "fields": {
-                            "keyword": {
-                                "type": "keyword",
-                                "ignore_above": 256
+                        },
+                        "method": { "type": "keyword" },
+                        "status": { "type": "short" },
+                        "cache_key": { "type": "keyword" },
+                        "timestamp": { "type": "date", "format": "epoch_second" },
+                        "headers": { "type": "object" },
+                        "body": { "type": "text" },
+                        // New fields for user analytics
+                        "user_id": { "type": "keyword" },
+                        "username": { "type": "keyword" },
+                        "client_ip": { "type": "ip" },
+                        "domain": { "type": "keyword" },
+                        "response_size": { "type": "long" },
+                        "request_duration_ms": { "type": "long" },
+                        "content_type": { "type": "keyword" },
+                        "user_agent": {
+                            "type": "text",
+                            "fields": {
+                                "keyword": {
+                                    "type": "keyword",
+                                    "ignore_above": 256
+                                }
                             }
                         }
                     }
+                },
+                "settings": {
+                    "number_of_shards": 1,
+                    "number_of_replicas": 0
                 }
-            },
-            "settings": {
-                "number_of_shards": 1,
-                "number_of_replicas": 0
             }
         });
 
+        self.opensearch
+            .indices()
+            .put_index_template(opensearch::indices::IndicesPutIndexTemplateParts::Name(&self.template_name()))
+            .body(template_body)
+            .send()
+            .await?;
+
+        info!("Uploaded index template '{}'", self.template_name());
+        Ok(())
+    }
+
+    /// Create `index_name` (inheriting mappings/settings from the index
+    /// template) and attach it to the stable `base_name` alias, unless it's
+    /// already known to exist. Safe to call once per batch: subsequent
+    /// calls for the same rolling index are served from `known_indices`.
+    async fn ensure_rolling_index(&self, index_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.known_indices.lock().unwrap().contains(index_name) {
+            return Ok(());
+        }
+
         match self
             .opensearch
             .indices()
-            .exists(opensearch::indices::IndicesExistsParts::Index(&[
-                &self.index_name
-            ]))
+            .exists(opensearch::indices::IndicesExistsParts::Index(&[index_name]))
             .send()
             .await
         {
-            Ok(response) => {
-                if response.status_code().is_success() {
-                    info!("Index '{}' already exists", self.index_name);
-                    return Ok(());
-                }
-            }
-            Err(e) => {
-                warn!("Error checking index existence: {}", e);
+            Ok(response) if response.status_code().is_success() => {
+                self.known_indices.lock().unwrap().insert(index_name.to_string());
+                return Ok(());
             }
+            Ok(_) => {}
+            Err(e) => warn!("Error checking existence of index '{}': {}", index_name, e),
         }
 
+        let body = json!({
+            "aliases": {
+                &self.base_name: {}
+            }
+        });
+
         match self
             .opensearch
             .indices()
-            .create(opensearch::indices::IndicesCreateParts::Index(
-                &self.index_name,
-            ))
-            .body(index_body)
+            .create(opensearch::indices::IndicesCreateParts::Index(index_name))
+            .body(body)
             .send()
             .await
         {
             Ok(_) => {
-                info!("Created index '{}'", self.index_name);
+                info!("Created rolling index '{}' (alias '{}')", index_name, self.base_name);
+                self.known_indices.lock().unwrap().insert(index_name.to_string());
                 Ok(())
             }
             Err(e) => {
-                error!("Failed to create index: {}", e);
+                error!("Failed to create rolling index '{}': {}", index_name, e);
                 Err(Box::new(e))
             }
         }
     }
 
-    async fn process_events(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Delete rolling indices older than `retention`, if configured.
+    async fn prune_old_indices(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(retention) = self.retention else { return Ok(()) };
+
+        let response = self
+            .opensearch
+            .cat()
+            .indices(opensearch::cat::CatIndicesParts::Index(&[&format!("{}-*", self.base_name)]))
+            .format("json")
+            .send()
+            .await?;
+        let entries: Vec<serde_json::Value> = response.json().await?;
+
+        let cutoff = Utc::now() - chrono::Duration::from_std(retention)?;
+        let prefix = format!("{}-", self.base_name);
+
+        for entry in entries {
+            let Some(name) = entry["index"].as_str() else { continue };
+            let Some(suffix) = name.strip_prefix(&prefix) else { continue };
+
+            // Hourly suffixes parse as a date via their leading `%Y.%m.%d`;
+            // the trailing `.%H` only narrows which hour of that day, which
+            // doesn't matter for a day-granularity retention cutoff.
+            let date_part = &suffix[..10.min(suffix.len())];
+            let Ok(index_date) = NaiveDate::parse_from_str(date_part, "%Y.%m.%d") else { continue };
+
+            if index_date.and_hms_opt(0, 0, 0).unwrap().and_utc() < cutoff {
+                match self
+                    .opensearch
+                    .indices()
+                    .delete(opensearch::indices::IndicesDeleteParts::Index(&[name]))
+                    .send()
+                    .await
+                {
+                    Ok(_) => {
+                        self.known_indices.lock().unwrap().remove(name);
+                        info!("Deleted rolling index '{}' past retention cutoff", name);
+                    }
+                    Err(e) => error!("Failed to delete expired index '{}': {}", name, e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consume and index events until `shutdown` is notified, at which point
+    /// the pending batch (if any) is flushed and committed before returning,
+    /// so a SIGTERM can't drop buffered events or leave the consumer offset
+    /// ahead of what was actually indexed.
+    async fn process_events(&self, shutdown: Arc<Notify>) -> Result<(), Box<dyn std::error::Error>> {
         let mut batch: Vec<CacheEvent> = Vec::new();
         let batch_size = 50;
         let batch_timeout = Duration::from_secs(5);
-        let last_commit = tokio::time::Instant::now();
+        let mut last_commit = tokio::time::Instant::now();
 
         loop {
-            match tokio::time::timeout(batch_timeout, self.consumer.recv()).await {
-                Ok(Ok(message)) => {
-                    if let Some(payload) = message.payload() {
-                        match serde_json::from_slice::<CacheEvent>(payload) {
-                            Ok(event) => {
-                                batch.push(event);
-
-                                if batch.len() >= batch_size {
-                                    self.index_batch(&batch).await?;
-                                    batch.clear();
-                                    self.consumer.commit_consumer_state(CommitMode::Async)?;
+            tokio::select! {
+                received = tokio::time::timeout(batch_timeout, self.consumer.recv()) => {
+                    match received {
+                        Ok(Ok(message)) => {
+                            if let Some(payload) = message.payload() {
+                                match serde_json::from_slice::<CacheEvent>(payload) {
+                                    Ok(event) => {
+                                        batch.push(event);
+
+                                        if batch.len() >= batch_size {
+                                            self.index_batch(&batch).await?;
+                                            batch.clear();
+                                            self.consumer.commit_consumer_state(CommitMode::Async)?;
+                                            last_commit = tokio::time::Instant::now();
+                                        }
+                                    }
+                                    Err(e) => {
+                                        warn!("Failed to parse event: {}", e);
+                                    }
                                 }
                             }
-                            Err(e) => {
-                                warn!("Failed to parse event: {}", e);
+                        }
+                        Ok(Err(e)) => {
+                            error!("Kafka error: {}", e);
+                        }
+                        Err(_) => {
+                            if !batch.is_empty() {
+                                self.index_batch(&batch).await?;
+                                batch.clear();
+                                self.consumer.commit_consumer_state(CommitMode::Async)?;
+                                last_commit = tokio::time::Instant::now();
                             }
                         }
                     }
                 }
-                Ok(Err(e)) => {
-                    error!("Kafka error: {}", e);
-                }
-                Err(_) => {
+                _ = shutdown.notified() => {
+                    info!("Shutdown received, flushing pending batch before exiting");
                     if !batch.is_empty() {
                         self.index_batch(&batch).await?;
                         batch.clear();
                         self.consumer.commit_consumer_state(CommitMode::Async)?;
                     }
+                    return Ok(());
                 }
             }
 
@@ -196,61 +360,199 @@ impl Indexer {
                 self.index_batch(&batch).await?;
                 batch.clear();
                 self.consumer.commit_consumer_state(CommitMode::Async)?;
+                last_commit = tokio::time::Instant::now();
             }
         }
     }
 
+    /// Index `events`, retrying individual failures (per the OpenSearch
+    /// bulk response's `items` array) with exponential backoff, and
+    /// dead-lettering whatever's still failing after `MAX_INDEX_RETRIES`
+    /// attempts. Returns once every event has either been indexed or
+    /// dead-lettered, so the caller can safely commit the Kafka offset.
     async fn index_batch(&self, events: &[CacheEvent]) -> Result<(), Box<dyn std::error::Error>> {
         if events.is_empty() {
             return Ok(());
         }
 
+        let mut pending: Vec<&CacheEvent> = events.iter().collect();
+        let mut attempt = 0u32;
+
+        loop {
+            let failures = self.send_bulk(&pending).await?;
+
+            if failures.is_empty() {
+                info!("Indexed {} events to OpenSearch", pending.len());
+                return Ok(());
+            }
+
+            if attempt >= MAX_INDEX_RETRIES {
+                warn!(
+                    "{} event(s) still failing after {} retries, sending to dead-letter topic '{}'",
+                    failures.len(),
+                    attempt,
+                    self.dead_letter_topic
+                );
+                for (event, reason) in failures {
+                    self.dead_letter_event(event, &reason).await?;
+                }
+                return Ok(());
+            }
+
+            attempt += 1;
+            let backoff = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            warn!(
+                "{} of {} event(s) failed bulk index (attempt {}/{}), retrying in {:?}",
+                failures.len(),
+                pending.len(),
+                attempt,
+                MAX_INDEX_RETRIES,
+                backoff
+            );
+            tokio::time::sleep(backoff).await;
+            pending = failures.into_iter().map(|(event, _)| event).collect();
+        }
+    }
+
+    /// Send one `_bulk` request for `events` and return the subset that
+    /// failed, each paired with the error OpenSearch reported for it. A
+    /// non-success HTTP response (or a request error) is treated as every
+    /// event in the batch failing with that status/error as the reason.
+    async fn send_bulk<'a>(
+        &self,
+        events: &[&'a CacheEvent],
+    ) -> Result<Vec<(&'a CacheEvent, String)>, Box<dyn std::error::Error>> {
         let mut body_lines: Vec<String> = Vec::new();
 
         for event in events {
+            let index_name = self.rollover.index_name(&self.base_name, event.timestamp);
+            self.ensure_rolling_index(&index_name).await?;
+
             let action = json!({
                 "index": {
-                    "_index": &self.index_name,
+                    "_index": index_name,
                     "_id": &event.cache_key
                 }
             });
             body_lines.push(serde_json::to_string(&action)?);
-
-            body_lines.push(serde_json::to_string(&event)?);
+            body_lines.push(serde_json::to_string(event)?);
         }
 
         let body_str = body_lines.join("\n") + "\n";
-
         let body = vec![body_str.into_bytes()];
 
-        match self
-            .opensearch
-            .bulk(BulkParts::None)
-            .body(body)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if response.status_code().is_success() {
-                    info!("Indexed {} events to OpenSearch", events.len());
-                } else {
-                    warn!(
-                        "Bulk index returned non-success status: {}",
-                        response.status_code()
-                    );
-                }
-                Ok(())
-            }
+        let response = match self.opensearch.bulk(BulkParts::None).body(body).send().await {
+            Ok(response) => response,
             Err(e) => {
-                error!("Failed to bulk index: {}", e);
+                error!("Bulk index request failed: {}", e);
+                return Ok(events.iter().map(|event| (*event, e.to_string())).collect());
+            }
+        };
+
+        if !response.status_code().is_success() {
+            let status = response.status_code();
+            warn!("Bulk index returned non-success status: {}", status);
+            return Ok(events
+                .iter()
+                .map(|event| (*event, format!("bulk request returned status {}", status)))
+                .collect());
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        let items = body["items"].as_array().cloned().unwrap_or_default();
+
+        let mut failures = Vec::new();
+        for (event, item) in events.iter().zip(items.iter()) {
+            // Each item is `{"index": {"status": ..., "error": {...}}}`;
+            // only `error`'s presence (not the status code alone) reliably
+            // distinguishes a per-document failure across OpenSearch
+            // versions.
+            if let Some(error) = item["index"]["error"].as_object() {
+                failures.push((*event, serde_json::Value::Object(error.clone()).to_string()));
+            }
+        }
+
+        Ok(failures)
+    }
+
+    /// Publish `event` plus `reason` to the dead-letter topic so it can be
+    /// inspected and replayed later instead of being silently dropped.
+    async fn dead_letter_event(
+        &self,
+        event: &CacheEvent,
+        reason: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let payload = json!({
+            "event": event,
+            "error": reason,
+        })
+        .to_string();
+
+        let record = FutureRecord::to(&self.dead_letter_topic)
+            .key(&event.cache_key)
+            .payload(&payload);
+
+        match self.producer.send(record, Duration::from_secs(5)).await {
+            Ok(_) => Ok(()),
+            Err((e, _)) => {
+                error!("Failed to publish dead-letter event for {}: {}", event.cache_key, e);
                 Err(Box::new(e))
             }
         }
     }
 
-    async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.ensure_index_exists().await?;
-        self.process_events().await
+    /// Periodically prune expired rolling indices. Never returns when
+    /// retention is disabled, so it doesn't race `process_events` to exit
+    /// `run`'s `select!`.
+    async fn maintenance_loop(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.retention.is_none() {
+            return std::future::pending().await;
+        }
+
+        let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.prune_old_indices().await {
+                error!("Failed to prune old indices: {}", e);
+            }
+        }
+    }
+
+    async fn run(&self, shutdown: Arc<Notify>) -> Result<(), Box<dyn std::error::Error>> {
+        self.ensure_template().await?;
+
+        let events = self.process_events(shutdown);
+        let maintenance = self.maintenance_loop();
+        tokio::pin!(events);
+        tokio::pin!(maintenance);
+
+        tokio::select! {
+            result = &mut events => result,
+            result = &mut maintenance => result,
+        }
+    }
+}
+
+/// Wait for SIGINT, SIGTERM, or (on Unix) SIGQUIT.
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler");
+        let mut sigquit = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::quit())
+            .expect("failed to install SIGQUIT handler");
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+            _ = sigquit.recv() => info!("Received SIGQUIT"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("Received Ctrl-C");
     }
 }
 
@@ -264,6 +566,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let kafka_topic = std::env::var("KAFKA_TOPIC").unwrap_or_else(|_| "cache-events".to_string());
     let kafka_group =
         std::env::var("KAFKA_GROUP_ID").unwrap_or_else(|_| "cache-indexer-group".to_string());
+    let dead_letter_topic = std::env::var("KAFKA_DEAD_LETTER_TOPIC")
+        .unwrap_or_else(|_| format!("{}-dlq", kafka_topic));
+    let rollover = RolloverGranularity::parse(
+        &std::env::var("INDEX_ROLLOVER_GRANULARITY").unwrap_or_else(|_| "daily".to_string()),
+    );
+    let retention = std::env::var("INDEX_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|days| Duration::from_secs(days * 24 * 3600));
     let index_name = "http-cache";
 
     info!("Starting cache-indexer");
@@ -271,6 +582,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("OpenSearch URL: {}", opensearch_url);
     info!("Kafka topic: {}", kafka_topic);
     info!("Kafka group: {}", kafka_group);
+    info!("Dead-letter topic: {}", dead_letter_topic);
+    info!("Index rollover: {:?}", rollover);
+    match retention {
+        Some(d) => info!("Index retention: {} day(s)", d.as_secs() / 86400),
+        None => info!("Index retention: disabled"),
+    }
 
     let indexer = Indexer::new(
         &kafka_brokers,
@@ -278,8 +595,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         &kafka_topic,
         &kafka_group,
         index_name,
+        &dead_letter_topic,
+        rollover,
+        retention,
     )
     .await?;
 
-    indexer.run().await
+    let shutdown = Arc::new(Notify::new());
+    let shutdown_signal = shutdown.clone();
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        shutdown_signal.notify_one();
+    });
+
+    indexer.run(shutdown).await
 }
@@ -1,3 +1,4 @@
+use cache_indexer::{CacheEvent, CacheEventIndexer, CacheEventIndexerConfig};
 use serde_json::json;
 use std::collections::HashMap;
 
@@ -5,17 +6,6 @@ use std::collections::HashMap;
 mod tests {
     use super::*;
 
-    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
-    struct CacheEvent {
-        url: String,
-        method: String,
-        status: u16,
-        cache_key: String,
-        timestamp: u64,
-        headers: HashMap<String, String>,
-        body: String,
-    }
-
     #[test]
     fn test_cache_event_serialization() {
         let mut headers = HashMap::new();
@@ -182,4 +172,62 @@ mod tests {
             assert!(code >= 500 && code < 600);
         }
     }
+
+    #[test]
+    fn test_indexer_bulk_body_matches_expected_ndjson_shape() {
+        let indexer = CacheEventIndexer::new(CacheEventIndexerConfig {
+            index: "http-cache".to_string(),
+            ..CacheEventIndexerConfig::default()
+        });
+
+        let events = vec![
+            CacheEvent {
+                url: "https://example.com/a".to_string(),
+                method: "GET".to_string(),
+                status: 200,
+                cache_key: "key-a".to_string(),
+                timestamp: 1234567890,
+                headers: HashMap::new(),
+                body: String::new(),
+            },
+            CacheEvent {
+                url: "https://example.com/b".to_string(),
+                method: "GET".to_string(),
+                status: 200,
+                cache_key: "key-b".to_string(),
+                timestamp: 1234567891,
+                headers: HashMap::new(),
+                body: String::new(),
+            },
+        ];
+
+        let body = indexer.build_bulk_body(&events);
+        let lines: Vec<&str> = body.trim_end_matches('\n').split('\n').collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(body.ends_with('\n'));
+
+        let first_action: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first_action["index"]["_index"], "http-cache");
+        assert_eq!(first_action["index"]["_id"], "key-a");
+
+        let first_doc: CacheEvent = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(first_doc, events[0]);
+
+        let second_action: serde_json::Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(second_action["index"]["_id"], "key-b");
+    }
+
+    #[test]
+    fn test_indexer_bulk_body_is_lone_newline_for_no_events() {
+        let indexer = CacheEventIndexer::new(CacheEventIndexerConfig::default());
+        assert_eq!(indexer.build_bulk_body(&[]), "\n");
+    }
+
+    #[test]
+    fn test_indexer_config_defaults_match_documented_batch_size() {
+        let config = CacheEventIndexerConfig::default();
+        assert_eq!(config.batch_size, 50);
+        assert_eq!(config.index, "http-cache");
+    }
 }
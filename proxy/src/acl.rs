@@ -8,13 +8,37 @@
 //! - Time-based access control
 //! - User/group-based rules
 
+use crate::category_db::CategoryDb;
+use crate::metrics::Metrics;
+use chrono::{Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, info, warn};
 
+/// Error returned when a rule is malformed and cannot be added to the engine.
+#[derive(Debug, Clone)]
+pub enum AclError {
+    /// A `TimeWindow` rule's `start`/`end`/`tz` field failed to parse, or a
+    /// `Regex` redirect rule's pattern is invalid or its `redirect_url`
+    /// references a capture group the pattern doesn't have.
+    InvalidRule(String),
+}
+
+impl std::fmt::Display for AclError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AclError::InvalidRule(reason) => write!(f, "invalid ACL rule: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AclError {}
+
 /// ACL action to take
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AclAction {
@@ -49,10 +73,21 @@ pub enum AclRuleType {
     Category(String),
     /// IP address range
     IpRange { start: IpAddr, end: IpAddr },
-    /// Time-based (cron-like)
-    TimeWindow { start: String, end: String },
+    /// Recurring time-of-day window, e.g. weekdays 09:00-17:00 in a given
+    /// IANA timezone (defaults to UTC). `start`/`end` are `HH:MM` 24-hour
+    /// strings; a window where `end <= start` wraps past midnight.
+    TimeWindow {
+        start: String,
+        end: String,
+        days: Vec<Weekday>,
+        tz: Option<String>,
+    },
     /// User or group
     Principal { user: Option<String>, group: Option<String> },
+    /// Combined host + path-prefix match, e.g. a single rule covering
+    /// `*.cdn.example.com` requests under `/private`. `host` supports the
+    /// same glob syntax as `Domain`.
+    UrlPath { host: String, path_prefix: Option<String> },
 }
 
 /// ACL rule
@@ -106,11 +141,181 @@ impl AclDecision {
     }
 }
 
+/// Parsed, validated form of a `TimeWindow` rule, cached by rule id so
+/// `check_access` never re-parses `start`/`end` or re-resolves the timezone.
+struct ParsedTimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    days: HashSet<Weekday>,
+    tz: Tz,
+}
+
+/// Parse and validate a `TimeWindow` rule's fields once, at add/load time.
+fn parse_time_window(start: &str, end: &str, days: &[Weekday], tz: Option<&str>) -> Result<ParsedTimeWindow, AclError> {
+    let start_time = NaiveTime::parse_from_str(start, "%H:%M")
+        .map_err(|_| AclError::InvalidRule(format!("invalid TimeWindow start '{}', expected HH:MM", start)))?;
+    let end_time = NaiveTime::parse_from_str(end, "%H:%M")
+        .map_err(|_| AclError::InvalidRule(format!("invalid TimeWindow end '{}', expected HH:MM", end)))?;
+    let tz: Tz = match tz {
+        Some(name) => name
+            .parse()
+            .map_err(|_| AclError::InvalidRule(format!("unknown IANA timezone '{}'", name)))?,
+        None => Tz::UTC,
+    };
+
+    Ok(ParsedTimeWindow {
+        start: start_time,
+        end: end_time,
+        days: days.iter().copied().collect(),
+        tz,
+    })
+}
+
+/// Compiled domain-matching strategy, cached per pattern string (mirroring
+/// `regex_cache`) so glob patterns aren't recompiled on every request.
+enum DomainMatcher {
+    /// No glob metacharacters - plain string compare.
+    Exact(String),
+    /// Legacy convenience syntax: `*.example.com` matches `example.com`
+    /// itself as well as any subdomain of it.
+    WildcardSubdomain(String),
+    /// General glob pattern (bare `*`, `?`, `[...]`) via `glob::Pattern`.
+    Glob(glob::Pattern),
+}
+
+impl DomainMatcher {
+    fn compile(pattern: &str) -> Self {
+        if let Some(suffix) = pattern.strip_prefix("*.") {
+            if !suffix.contains(['*', '?', '[', ']']) {
+                return DomainMatcher::WildcardSubdomain(suffix.to_string());
+            }
+        }
+
+        if pattern.contains(['*', '?', '[', ']']) {
+            match glob::Pattern::new(pattern) {
+                Ok(compiled) => return DomainMatcher::Glob(compiled),
+                Err(e) => warn!("Invalid domain glob pattern '{}': {}", pattern, e),
+            }
+        }
+
+        DomainMatcher::Exact(pattern.to_string())
+    }
+
+    fn matches(&self, domain: &str) -> bool {
+        match self {
+            DomainMatcher::Exact(exact) => domain == exact,
+            DomainMatcher::WildcardSubdomain(suffix) => {
+                domain == suffix || domain.ends_with(&format!(".{}", suffix))
+            }
+            DomainMatcher::Glob(pattern) => pattern.matches(domain),
+        }
+    }
+}
+
+/// Extract every `$1`, `$2`, `${name}` capture-group reference from a
+/// redirect template, for validating them against a regex's actual groups.
+fn capture_refs(template: &str) -> Vec<String> {
+    let bytes = template.as_bytes();
+    let mut refs = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'$' || i + 1 >= bytes.len() {
+            i += 1;
+            continue;
+        }
+
+        if bytes[i + 1] == b'{' {
+            if let Some(len) = template[i + 2..].find('}') {
+                refs.push(template[i + 2..i + 2 + len].to_string());
+                i += 2 + len + 1;
+                continue;
+            }
+        } else if bytes[i + 1].is_ascii_digit() {
+            let start = i + 1;
+            let mut end = start;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+            refs.push(template[start..end].to_string());
+            i = end;
+            continue;
+        }
+
+        i += 1;
+    }
+
+    refs
+}
+
+/// Validate that every `$N`/`${name}` capture-group reference in a `Regex`
+/// rule's redirect template actually exists in the pattern, so a typo'd
+/// group number fails at load time rather than silently expanding to empty.
+fn validate_redirect_template(pattern: &str, template: &str) -> Result<(), AclError> {
+    let regex = Regex::new(pattern)
+        .map_err(|e| AclError::InvalidRule(format!("invalid regex pattern '{}': {}", pattern, e)))?;
+    let group_count = regex.captures_len() - 1;
+    let named_groups: HashSet<&str> = regex.capture_names().flatten().collect();
+
+    for reference in capture_refs(template) {
+        let valid = match reference.parse::<usize>() {
+            Ok(index) => index <= group_count,
+            Err(_) => named_groups.contains(reference.as_str()),
+        };
+        if !valid {
+            return Err(AclError::InvalidRule(format!(
+                "redirect_url references capture group '{}' which doesn't exist in pattern '{}'",
+                reference, pattern
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract the path (plus query string) from a full URL, e.g.
+/// `"https://example.com/a/b?x=1"` -> `"/a/b?x=1"`. Returns `None` if the
+/// URL has no path component at all.
+fn url_path(url: &str) -> Option<&str> {
+    let after_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    after_scheme.find('/').map(|idx| &after_scheme[idx..])
+}
+
+/// Resolves which groups a user belongs to, for `Principal` ACL rules that
+/// target a `group` rather than (or in addition to) a specific `user`.
+pub trait GroupResolver: Send + Sync {
+    fn groups_for(&self, user: &str) -> Vec<String>;
+}
+
+/// `GroupResolver` backed by a static, config-loaded user->groups map.
+/// Sufficient for small deployments; an LDAP- or directory-backed resolver
+/// can implement the same trait for dynamic membership lookups.
+pub struct StaticGroupResolver {
+    memberships: HashMap<String, Vec<String>>,
+}
+
+impl StaticGroupResolver {
+    pub fn new(memberships: HashMap<String, Vec<String>>) -> Self {
+        Self { memberships }
+    }
+}
+
+impl GroupResolver for StaticGroupResolver {
+    fn groups_for(&self, user: &str) -> Vec<String> {
+        self.memberships.get(user).cloned().unwrap_or_default()
+    }
+}
+
 /// ACL engine
 pub struct AclEngine {
     rules: Vec<AclRule>,
     default_action: AclAction,
     regex_cache: HashMap<String, Regex>,
+    time_window_cache: HashMap<String, ParsedTimeWindow>,
+    domain_pattern_cache: HashMap<String, DomainMatcher>,
+    group_resolver: Option<Arc<dyn GroupResolver>>,
+    category_db: Option<Arc<CategoryDb>>,
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl AclEngine {
@@ -120,21 +325,75 @@ impl AclEngine {
             rules: Vec::new(),
             default_action,
             regex_cache: HashMap::new(),
+            time_window_cache: HashMap::new(),
+            domain_pattern_cache: HashMap::new(),
+            group_resolver: None,
+            category_db: None,
+            metrics: None,
         }
     }
 
+    /// Install a group resolver used to evaluate `Principal` rules that
+    /// target a `group` rather than (or in addition to) a specific `user`.
+    pub fn set_group_resolver(&mut self, resolver: Arc<dyn GroupResolver>) {
+        self.group_resolver = Some(resolver);
+    }
+
+    /// Install a category database used to resolve `Category` rules when the
+    /// caller doesn't already know the category for a request's domain.
+    pub fn set_category_db(&mut self, db: Arc<CategoryDb>) {
+        self.category_db = Some(db);
+    }
+
+    /// Install a metrics handle so every `check_access` call records an
+    /// `acl_decisions_total` increment and `acl_evaluation_duration_seconds`
+    /// observation.
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
     /// Add ACL rule
-    pub fn add_rule(&mut self, rule: AclRule) {
+    pub fn add_rule(&mut self, rule: AclRule) -> Result<(), AclError> {
         info!("Adding ACL rule: {} (priority: {})", rule.name, rule.priority);
+
+        if let AclRuleType::TimeWindow { start, end, days, tz } = &rule.rule_type {
+            let parsed = parse_time_window(start, end, days, tz.as_deref())?;
+            self.time_window_cache.insert(rule.id.clone(), parsed);
+        }
+
+        if let (AclRuleType::Regex(pattern), AclAction::Redirect, Some(template)) =
+            (&rule.rule_type, rule.action, &rule.redirect_url)
+        {
+            validate_redirect_template(pattern, template)?;
+        }
+
         self.rules.push(rule);
         self.rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+        Ok(())
     }
 
     /// Load rules from configuration
-    pub fn load_rules(&mut self, rules: Vec<AclRule>) {
+    pub fn load_rules(&mut self, rules: Vec<AclRule>) -> Result<(), AclError> {
         info!("Loading {} ACL rules", rules.len());
+
+        let mut time_window_cache = HashMap::new();
+        for rule in &rules {
+            if let AclRuleType::TimeWindow { start, end, days, tz } = &rule.rule_type {
+                let parsed = parse_time_window(start, end, days, tz.as_deref())?;
+                time_window_cache.insert(rule.id.clone(), parsed);
+            }
+
+            if let (AclRuleType::Regex(pattern), AclAction::Redirect, Some(template)) =
+                (&rule.rule_type, rule.action, &rule.redirect_url)
+            {
+                validate_redirect_template(pattern, template)?;
+            }
+        }
+
+        self.time_window_cache = time_window_cache;
         self.rules = rules;
         self.rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+        Ok(())
     }
 
     /// Check if request is allowed
@@ -145,17 +404,64 @@ impl AclEngine {
         category: Option<&str>,
         user: Option<&str>,
         client_ip: Option<IpAddr>,
+        user_groups: Option<&[String]>,
     ) -> AclDecision {
-        debug!("ACL check: url={}, domain={}, category={:?}, user={:?}", 
+        debug!("ACL check: url={}, domain={}, category={:?}, user={:?}",
                url, domain, category, user);
 
+        let start = Instant::now();
+        let decision = self.evaluate(url, domain, category, user, client_ip, user_groups);
+
+        if let Some(metrics) = &self.metrics {
+            metrics
+                .acl_evaluation_duration_seconds
+                .observe(start.elapsed().as_secs_f64());
+            metrics
+                .acl_decisions_total
+                .with_label_values(&[
+                    &decision.action.to_string(),
+                    decision.rule_id.as_deref().unwrap_or("none"),
+                ])
+                .inc();
+        }
+
+        decision
+    }
+
+    fn evaluate(
+        &mut self,
+        url: &str,
+        domain: &str,
+        category: Option<&str>,
+        user: Option<&str>,
+        client_ip: Option<IpAddr>,
+        user_groups: Option<&[String]>,
+    ) -> AclDecision {
+        // Resolve the user's groups once up front (if not already supplied
+        // by the caller) so `matches_rule` never triggers a lookup per rule.
+        let resolved_groups: Option<Vec<String>> = match (user_groups, user, &self.group_resolver) {
+            (Some(groups), _, _) => Some(groups.to_vec()),
+            (None, Some(u), Some(resolver)) => Some(resolver.groups_for(u)),
+            _ => None,
+        };
+        let user_groups = resolved_groups.as_deref();
+
+        // Resolve the domain's category once up front too, if the caller
+        // didn't already classify it and a `CategoryDb` is configured.
+        let resolved_category: Option<String> = match (category, &self.category_db) {
+            (Some(c), _) => Some(c.to_string()),
+            (None, Some(db)) => db.categorize(domain),
+            (None, None) => None,
+        };
+        let category = resolved_category.as_deref();
+
         // Check each rule in priority order
         for rule in &self.rules {
             if !rule.enabled {
                 continue;
             }
 
-            if self.matches_rule(rule, url, domain, category, user, client_ip) {
+            if self.matches_rule(rule, url, domain, category, user, client_ip, user_groups) {
                 debug!("Matched ACL rule: {} ({})", rule.name, rule.id);
                 
                 return match rule.action {
@@ -166,7 +472,7 @@ impl AclEngine {
                     ),
                     AclAction::Redirect => AclDecision::redirect(
                         rule.id.clone(),
-                        rule.redirect_url.clone().unwrap_or_default(),
+                        self.expand_redirect_url(rule, url).unwrap_or_default(),
                         format!("Redirected by rule: {}", rule.name),
                     ),
                 };
@@ -197,6 +503,7 @@ impl AclEngine {
         category: Option<&str>,
         user: Option<&str>,
         client_ip: Option<IpAddr>,
+        user_groups: Option<&[String]>,
     ) -> bool {
         match &rule.rule_type {
             AclRuleType::Domain(pattern) => self.match_domain(domain, pattern),
@@ -212,32 +519,75 @@ impl AclEngine {
                     false
                 }
             }
-            AclRuleType::TimeWindow { start: _, end: _ } => {
-                // TODO: Implement time-based matching
-                true
+            AclRuleType::TimeWindow { .. } => {
+                match self.time_window_cache.get(&rule.id) {
+                    Some(window) => self.in_time_window(window),
+                    None => {
+                        warn!("TimeWindow rule {} has no cached schedule, denying match", rule.id);
+                        false
+                    }
+                }
             }
-            AclRuleType::Principal { user: rule_user, group: _ } => {
-                if let Some(u) = user {
-                    rule_user.as_ref().map(|ru| ru == u).unwrap_or(false)
-                } else {
-                    false
+            AclRuleType::Principal { user: rule_user, group: rule_group } => {
+                let user_matches = match (user, rule_user) {
+                    (Some(u), Some(ru)) => u == ru,
+                    _ => false,
+                };
+
+                let group_matches = match (user_groups, rule_group) {
+                    (Some(groups), Some(rg)) => groups.iter().any(|g| g == rg),
+                    _ => false,
+                };
+
+                user_matches || group_matches
+            }
+            AclRuleType::UrlPath { host, path_prefix } => {
+                if !self.match_domain(domain, host) {
+                    return false;
+                }
+                match path_prefix {
+                    Some(prefix) => url_path(url).map(|path| path.starts_with(prefix.as_str())).unwrap_or(false),
+                    None => true,
                 }
             }
         }
     }
 
-    /// Match domain pattern (supports wildcards)
-    fn match_domain(&self, domain: &str, pattern: &str) -> bool {
-        if pattern.starts_with("*.") {
-            // Wildcard subdomain match
-            let suffix = &pattern[2..];
-            domain.ends_with(suffix) || domain == suffix
-        } else if pattern.starts_with('*') {
-            // Wildcard suffix match
-            domain.ends_with(&pattern[1..])
-        } else {
-            // Exact match
-            domain == pattern
+    /// Match a domain against a rule pattern. Supports a leading `*.`
+    /// (subdomain-or-self), bare glob metacharacters (`*`, `?`, `[...]`) via
+    /// a cached `glob::Pattern`, or a plain exact match.
+    fn match_domain(&mut self, domain: &str, pattern: &str) -> bool {
+        let matcher = self.domain_pattern_cache
+            .entry(pattern.to_string())
+            .or_insert_with(|| DomainMatcher::compile(pattern));
+
+        matcher.matches(domain)
+    }
+
+    /// Build the final redirect URL for a matched rule. For `Regex` rules,
+    /// substitutes `$1`, `$2`, `${name}` references in `redirect_url` from
+    /// the matching capture groups via `Captures::expand`; every other rule
+    /// type just returns the static `redirect_url` unchanged.
+    fn expand_redirect_url(&mut self, rule: &AclRule, url: &str) -> Option<String> {
+        let template = rule.redirect_url.as_ref()?;
+        let AclRuleType::Regex(pattern) = &rule.rule_type else {
+            return Some(template.clone());
+        };
+
+        let regex = self.regex_cache.entry(pattern.clone()).or_insert_with(|| {
+            Regex::new(pattern).unwrap_or_else(|e| {
+                warn!("Invalid regex pattern '{}': {}", pattern, e);
+                Regex::new("(?!)").expect("Failed to create never-matching regex")
+            })
+        });
+
+        match regex.captures(url) {
+            Some(captures) => {
+                let mut expanded = String::new();
+                captures.expand(template, &mut expanded);
+                Some(expanded)
+            }
+            None => Some(template.clone()),
         }
     }
 
@@ -273,6 +623,25 @@ impl AclEngine {
             _ => false,
         }
     }
+
+    /// Test whether "now", resolved in the window's timezone, falls within
+    /// `[start, end)` on an allowed day. Windows where `end <= start` wrap
+    /// past midnight and are tested as two intervals: `[start, 24:00)` on
+    /// the current day and `[00:00, end)` carried over from the previous day.
+    fn in_time_window(&self, window: &ParsedTimeWindow) -> bool {
+        let now = Utc::now().with_timezone(&window.tz);
+        let today = now.weekday();
+        let time = now.time();
+
+        if window.start < window.end {
+            window.days.contains(&today) && time >= window.start && time < window.end
+        } else {
+            // Wraps past midnight: either still within today's late leg,
+            // or within the early leg that started on the previous day.
+            (window.days.contains(&today) && time >= window.start)
+                || (window.days.contains(&today.pred()) && time < window.end)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -294,6 +663,57 @@ mod tests {
         assert!(!engine.match_domain("example.org", "*.example.com"));
     }
 
+    #[test]
+    fn test_domain_glob_matching() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        assert!(engine.match_domain("api-1.example.com", "api-?.example.com"));
+        assert!(engine.match_domain("api-2.example.com", "api-?.example.com"));
+        assert!(!engine.match_domain("api-12.example.com", "api-?.example.com"));
+
+        assert!(engine.match_domain("a.cdn.us.net", "*.cdn.[a-z]*.net"));
+        assert!(!engine.match_domain("a.cdn.US.net", "*.cdn.[a-z]*.net"));
+    }
+
+    #[test]
+    fn test_url_path_rule() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        engine.add_rule(AclRule {
+            id: "private-cdn".to_string(),
+            name: "Block private CDN paths".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Deny,
+            rule_type: AclRuleType::UrlPath {
+                host: "*.cdn.example.com".to_string(),
+                path_prefix: Some("/private".to_string()),
+            },
+            redirect_url: None,
+            comment: None,
+        }).unwrap();
+
+        let blocked = engine.check_access(
+            "https://assets.cdn.example.com/private/secrets.json",
+            "assets.cdn.example.com",
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(blocked.action, AclAction::Deny);
+
+        let allowed = engine.check_access(
+            "https://assets.cdn.example.com/public/logo.png",
+            "assets.cdn.example.com",
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(allowed.action, AclAction::Allow);
+    }
+
     #[test]
     fn test_url_prefix() {
         let mut engine = AclEngine::new(AclAction::Allow);
@@ -307,7 +727,7 @@ mod tests {
             rule_type: AclRuleType::UrlPrefix("https://example.com/admin".to_string()),
             redirect_url: None,
             comment: None,
-        });
+        }).unwrap();
         
         let decision = engine.check_access(
             "https://example.com/admin/users",
@@ -315,6 +735,7 @@ mod tests {
             None,
             None,
             None,
+            None,
         );
         
         assert_eq!(decision.action, AclAction::Deny);
@@ -333,7 +754,7 @@ mod tests {
             rule_type: AclRuleType::Category("adult".to_string()),
             redirect_url: None,
             comment: None,
-        });
+        }).unwrap();
         
         let decision = engine.check_access(
             "https://example.com",
@@ -341,6 +762,7 @@ mod tests {
             Some("adult"),
             None,
             None,
+            None,
         );
         
         assert_eq!(decision.action, AclAction::Deny);
@@ -360,7 +782,7 @@ mod tests {
             rule_type: AclRuleType::Domain("*.example.com".to_string()),
             redirect_url: None,
             comment: None,
-        });
+        }).unwrap();
         
         // Higher priority (deny)
         engine.add_rule(AclRule {
@@ -372,7 +794,7 @@ mod tests {
             rule_type: AclRuleType::Domain("admin.example.com".to_string()),
             redirect_url: None,
             comment: None,
-        });
+        }).unwrap();
         
         // Should match high priority deny rule
         let decision = engine.check_access(
@@ -381,9 +803,200 @@ mod tests {
             None,
             None,
             None,
+            None,
         );
         
         assert_eq!(decision.action, AclAction::Deny);
         assert_eq!(decision.rule_id.unwrap(), "high");
     }
+
+    #[test]
+    fn test_time_window_matches_all_day_every_day() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        engine.add_rule(AclRule {
+            id: "always".to_string(),
+            name: "Block all day".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Deny,
+            rule_type: AclRuleType::TimeWindow {
+                start: "00:00".to_string(),
+                end: "00:00".to_string(),
+                days: vec![
+                    Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu,
+                    Weekday::Fri, Weekday::Sat, Weekday::Sun,
+                ],
+                tz: None,
+            },
+            redirect_url: None,
+            comment: None,
+        }).unwrap();
+
+        let decision = engine.check_access("https://example.com", "example.com", None, None, None, None);
+        assert_eq!(decision.action, AclAction::Deny);
+    }
+
+    #[test]
+    fn test_time_window_wraps_past_midnight() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        // Every day of the week is allowed, so only the start/end wraparound
+        // logic itself is under test here, not day-of-week filtering.
+        let all_days = vec![
+            Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu,
+            Weekday::Fri, Weekday::Sat, Weekday::Sun,
+        ];
+
+        engine.add_rule(AclRule {
+            id: "overnight".to_string(),
+            name: "Block overnight".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Deny,
+            rule_type: AclRuleType::TimeWindow {
+                start: "22:00".to_string(),
+                end: "06:00".to_string(),
+                days: all_days,
+                tz: Some("UTC".to_string()),
+            },
+            redirect_url: None,
+            comment: None,
+        }).unwrap();
+
+        // Any time of day falls in [22:00, 24:00) or [00:00, 06:00) except
+        // the [06:00, 22:00) gap; since every day is allowed, the only way
+        // this rule can fail to match "now" is if it's currently daytime.
+        let now_utc = Utc::now().time();
+        let expect_match = now_utc >= NaiveTime::from_hms_opt(22, 0, 0).unwrap()
+            || now_utc < NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+
+        let decision = engine.check_access("https://example.com", "example.com", None, None, None, None);
+        assert_eq!(decision.action == AclAction::Deny, expect_match);
+    }
+
+    #[test]
+    fn test_time_window_rejects_malformed_input() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        let result = engine.add_rule(AclRule {
+            id: "bad".to_string(),
+            name: "Bad schedule".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Deny,
+            rule_type: AclRuleType::TimeWindow {
+                start: "not-a-time".to_string(),
+                end: "06:00".to_string(),
+                days: vec![Weekday::Mon],
+                tz: None,
+            },
+            redirect_url: None,
+            comment: None,
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_principal_group_matching() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        let mut memberships = HashMap::new();
+        memberships.insert("alice".to_string(), vec!["contractors".to_string()]);
+        engine.set_group_resolver(Arc::new(StaticGroupResolver::new(memberships)));
+
+        engine.add_rule(AclRule {
+            id: "deny-contractors".to_string(),
+            name: "Deny contractors".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Deny,
+            rule_type: AclRuleType::Principal { user: None, group: Some("contractors".to_string()) },
+            redirect_url: None,
+            comment: None,
+        }).unwrap();
+
+        let denied = engine.check_access(
+            "https://example.com", "example.com", None, Some("alice"), None, None,
+        );
+        assert_eq!(denied.action, AclAction::Deny);
+
+        let allowed = engine.check_access(
+            "https://example.com", "example.com", None, Some("bob"), None, None,
+        );
+        assert_eq!(allowed.action, AclAction::Allow);
+    }
+
+    #[test]
+    fn test_regex_redirect_capture_groups() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        engine.add_rule(AclRule {
+            id: "migrate-old-site".to_string(),
+            name: "Redirect old site to new".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Redirect,
+            rule_type: AclRuleType::Regex(r"^https://old\.example\.com/(?P<path>.*)$".to_string()),
+            redirect_url: Some("https://new.example.com/${path}".to_string()),
+            comment: None,
+        }).unwrap();
+
+        let decision = engine.check_access(
+            "https://old.example.com/articles/42",
+            "old.example.com",
+            None,
+            None,
+            None,
+            None,
+        );
+
+        assert_eq!(decision.action, AclAction::Redirect);
+        assert_eq!(decision.redirect_url.as_deref(), Some("https://new.example.com/articles/42"));
+    }
+
+    #[test]
+    fn test_regex_redirect_rejects_unknown_capture_group() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        let result = engine.add_rule(AclRule {
+            id: "bad-redirect".to_string(),
+            name: "Bad redirect template".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Redirect,
+            rule_type: AclRuleType::Regex(r"^https://old\.example\.com/(.*)$".to_string()),
+            redirect_url: Some("https://new.example.com/$2".to_string()),
+            comment: None,
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_category_resolved_from_category_db() {
+        let mut engine = AclEngine::new(AclAction::Allow);
+
+        let mut db = crate::category_db::CategoryDb::new();
+        db.insert("ads.example.com", "adv");
+        engine.set_category_db(Arc::new(db));
+
+        engine.add_rule(AclRule {
+            id: "block-adv".to_string(),
+            name: "Block advertising".to_string(),
+            enabled: true,
+            priority: 100,
+            action: AclAction::Deny,
+            rule_type: AclRuleType::Category("adv".to_string()),
+            redirect_url: None,
+            comment: None,
+        }).unwrap();
+
+        // No `category` passed in - the engine must resolve it itself.
+        let decision = engine.check_access(
+            "https://ads.example.com/banner", "ads.example.com", None, None, None, None,
+        );
+        assert_eq!(decision.action, AclAction::Deny);
+    }
 }
@@ -4,17 +4,270 @@
 //! - Shallalist (open-source category database)
 //! - URLhaus (malware URLs)
 //! - PhishTank (phishing detection)
+//! - DNSBL/URIBL zones (Spamhaus DBL, SURBL, ...)
 //! - Custom database
 
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use hickory_resolver::config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::TokioAsyncResolver;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use std::hash::{Hash, Hasher};
+use std::io::Read as _;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock as StdRwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Notify, RwLock};
 use tracing::{debug, error, info, warn};
 use url::Url;
 
+/// A small set of common multi-label public suffixes, enough to keep DNSBL
+/// lookups from treating e.g. `example.co.uk` as if `co.uk` itself were the
+/// registrable domain. Not a full public suffix list - just the handful of
+/// second-level suffixes likely to show up in proxy traffic.
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "co.jp", "co.in", "co.nz", "com.au", "com.br", "com.mx",
+];
+
+/// Reduce `domain` to its registrable domain (the label immediately under
+/// the public suffix plus the suffix itself), since querying a full
+/// subdomain against a DNSBL/URIBL zone breaks most blocklists. Returns
+/// `None` for a bare TLD or single-label input.
+fn registrable_domain(domain: &str) -> Option<String> {
+    let labels: Vec<&str> = domain.split('.').filter(|l| !l.is_empty()).collect();
+    if labels.len() < 2 {
+        return None;
+    }
+
+    let last_two = labels[labels.len() - 2..].join(".");
+    if labels.len() >= 3 && MULTI_LABEL_SUFFIXES.contains(&last_two.as_str()) {
+        return Some(labels[labels.len() - 3..].join("."));
+    }
+
+    Some(last_two)
+}
+
+/// A DNSBL/URIBL zone to query (e.g. `dbl.spamhaus.org`, `multi.surbl.org`).
+/// A `127.0.0.x` answer's last octet is a bitmask of listing reasons;
+/// `bit_categories` maps each bit to the `Category` it represents. A set
+/// bit with no entry here still counts as "listed" under a generic
+/// `Category::Custom` tag.
+#[derive(Debug, Clone)]
+pub struct ZoneConfig {
+    pub zone: String,
+    pub bit_categories: HashMap<u8, Category>,
+    /// Whether to additionally fetch the zone's TXT record for a
+    /// human-readable listing reason once a hit is confirmed.
+    pub fetch_reason: bool,
+}
+
+/// Fixed-size Bloom filter used to hold the full URLhaus malware feed in
+/// bounded memory with O(1) membership checks. False positives are
+/// expected and must be confirmed against the live URLhaus API before
+/// being treated as a hit; false negatives are not possible.
+struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: usize,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Size the filter for `expected_items` entries at `false_positive_rate`.
+    fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let expected_items = expected_items.max(1);
+        let num_bits = Self::optimal_num_bits(expected_items, false_positive_rate);
+        let num_hashes = Self::optimal_num_hashes(expected_items, num_bits);
+
+        Self {
+            bits: vec![0u64; num_bits.div_ceil(64)],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn optimal_num_bits(n: usize, p: f64) -> usize {
+        let m = -(n as f64 * p.ln()) / std::f64::consts::LN_2.powi(2);
+        (m.ceil() as usize).max(64)
+    }
+
+    fn optimal_num_hashes(n: usize, num_bits: usize) -> u32 {
+        let k = (num_bits as f64 / n as f64) * std::f64::consts::LN_2;
+        (k.round() as u32).clamp(1, 16)
+    }
+
+    /// Double hashing (Kirsch-Mitzenmacher): derive all `num_hashes` bit
+    /// positions from two independent hashes instead of `num_hashes`
+    /// separate hash functions.
+    fn hashes(item: &str) -> (u64, u64) {
+        let mut h1 = DefaultHasher::new();
+        item.hash(&mut h1);
+
+        let mut h2 = DefaultHasher::new();
+        item.hash(&mut h2);
+        0x9E3779B97F4A7C15u64.hash(&mut h2);
+
+        (h1.finish(), h2.finish())
+    }
+
+    fn bit_index(h1: u64, h2: u64, i: u32, num_bits: usize) -> usize {
+        (h1.wrapping_add((i as u64).wrapping_mul(h2)) as usize) % num_bits
+    }
+
+    fn insert(&mut self, item: &str) {
+        let (h1, h2) = Self::hashes(item);
+        for i in 0..self.num_hashes {
+            let bit = Self::bit_index(h1, h2, i, self.num_bits);
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, item: &str) -> bool {
+        let (h1, h2) = Self::hashes(item);
+        (0..self.num_hashes).all(|i| {
+            let bit = Self::bit_index(h1, h2, i, self.num_bits);
+            self.bits[bit / 64] & (1 << (bit % 64)) != 0
+        })
+    }
+}
+
+/// Parse a Shallalist directory already extracted to disk: one
+/// subdirectory per category, each containing a `domains` file.
+fn parse_shallalist_dir(path: &str) -> Result<HashMap<String, HashSet<Category>>, String> {
+    let mut db = HashMap::new();
+    let categories_dir = std::path::Path::new(path);
+
+    if !categories_dir.exists() {
+        return Err(format!("Shallalist directory not found: {}", path));
+    }
+
+    for entry in
+        std::fs::read_dir(categories_dir).map_err(|e| format!("Failed to read directory: {}", e))?
+    {
+        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+        let category_name = entry.file_name().to_string_lossy().to_string();
+        let category = Category::from_str(&category_name);
+
+        let domains_file = entry.path().join("domains");
+        if domains_file.exists() {
+            let content = std::fs::read_to_string(&domains_file)
+                .map_err(|e| format!("Failed to read domains file: {}", e))?;
+            insert_shallalist_domains(&mut db, &category, &content);
+        }
+    }
+
+    Ok(db)
+}
+
+/// Parse a Shallalist tarball (gzip-compressed tar of `category/domains`
+/// entries), as served by the upstream tarball feed.
+fn parse_shallalist_tarball(bytes: &[u8]) -> Result<HashMap<String, HashSet<Category>>, String> {
+    let mut db = HashMap::new();
+    let decoder = GzDecoder::new(bytes);
+    let mut archive = tar::Archive::new(decoder);
+
+    for entry in archive.entries().map_err(|e| format!("Failed to read tarball: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Failed to read tarball entry: {}", e))?;
+        let entry_path = entry
+            .path()
+            .map_err(|e| format!("Invalid tarball entry path: {}", e))?
+            .into_owned();
+
+        if entry_path.file_name().and_then(|n| n.to_str()) != Some("domains") {
+            continue;
+        }
+
+        let Some(category_name) =
+            entry_path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str())
+        else {
+            continue;
+        };
+        let category = Category::from_str(category_name);
+
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .map_err(|e| format!("Failed to read domains entry: {}", e))?;
+        insert_shallalist_domains(&mut db, &category, &content);
+    }
+
+    Ok(db)
+}
+
+fn insert_shallalist_domains(
+    db: &mut HashMap<String, HashSet<Category>>,
+    category: &Category,
+    content: &str,
+) {
+    for line in content.lines() {
+        let domain = line.trim();
+        if !domain.is_empty() && !domain.starts_with('#') {
+            db.entry(domain.to_string()).or_insert_with(HashSet::new).insert(category.clone());
+        }
+    }
+}
+
+/// Fetch and parse the Shallalist tarball feed over HTTP.
+async fn fetch_shallalist_feed(
+    http_client: &Client,
+    url: &str,
+) -> Result<HashMap<String, HashSet<Category>>, String> {
+    let bytes = http_client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch Shallalist feed: {}", e))?
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read Shallalist feed body: {}", e))?;
+
+    parse_shallalist_tarball(&bytes)
+}
+
+/// Fetch the full URLhaus host/URL dump and build a Bloom filter of its
+/// entries, sized per `expected_items`/`false_positive_rate`.
+async fn fetch_urlhaus_bloom(
+    http_client: &Client,
+    url: &str,
+    expected_items: usize,
+    false_positive_rate: f64,
+) -> Result<BloomFilter, String> {
+    let body = http_client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch URLhaus feed: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read URLhaus feed body: {}", e))?;
+
+    let mut bloom = BloomFilter::new(expected_items, false_positive_rate);
+    let mut count = 0usize;
+
+    // URLhaus CSV dump columns: id,dateadded,url,url_status,threat,tags,urlhaus_link,reporter
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some(entry_url) = line.split(',').nth(2) else { continue };
+        let entry_url = entry_url.trim_matches('"');
+        if entry_url.is_empty() {
+            continue;
+        }
+
+        bloom.insert(entry_url);
+        count += 1;
+    }
+
+    debug!("Indexed {} URLhaus feed entries into Bloom filter", count);
+    Ok(bloom)
+}
+
 /// URL category
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Category {
@@ -100,17 +353,147 @@ pub struct CategorizationResult {
     pub cached: bool,
 }
 
-/// Cached category entry
-#[derive(Clone)]
+/// Cached category entry. Uses `SystemTime` rather than `Instant` so it can
+/// survive a round trip through a persistent `CacheStore`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CategoryCache {
     categories: HashSet<Category>,
-    cached_at: Instant,
+    cached_at: SystemTime,
     ttl: Duration,
 }
 
 impl CategoryCache {
     fn is_expired(&self) -> bool {
-        self.cached_at.elapsed() > self.ttl
+        SystemTime::now()
+            .duration_since(self.cached_at)
+            .map(|elapsed| elapsed > self.ttl)
+            .unwrap_or(false)
+    }
+}
+
+/// A pluggable store for categorization verdicts, keyed by domain. TTL is
+/// always honored on read: an implementation must not return an expired
+/// entry from `get`.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, domain: &str) -> Option<CategoryCache>;
+    async fn put(&self, domain: &str, entry: CategoryCache);
+    async fn cleanup_expired(&self);
+}
+
+/// In-memory cache store. Verdicts are lost on restart.
+struct InMemoryCacheStore {
+    entries: RwLock<HashMap<String, CategoryCache>>,
+}
+
+impl InMemoryCacheStore {
+    fn new() -> Self {
+        Self { entries: RwLock::new(HashMap::new()) }
+    }
+}
+
+#[async_trait]
+impl CacheStore for InMemoryCacheStore {
+    async fn get(&self, domain: &str) -> Option<CategoryCache> {
+        let entries = self.entries.read().await;
+        entries.get(domain).filter(|entry| !entry.is_expired()).cloned()
+    }
+
+    async fn put(&self, domain: &str, entry: CategoryCache) {
+        self.entries.write().await.insert(domain.to_string(), entry);
+    }
+
+    async fn cleanup_expired(&self) {
+        self.entries.write().await.retain(|_, entry| !entry.is_expired());
+    }
+}
+
+/// `sled`-backed cache store: entries are bincode-serialized and keyed by
+/// domain, so verdicts survive a proxy restart. `sled` is a synchronous
+/// embedded DB, so every operation runs on the blocking pool.
+struct SledCacheStore {
+    db: sled::Db,
+}
+
+impl SledCacheStore {
+    fn open(path: &str) -> sled::Result<Self> {
+        Ok(Self { db: sled::open(path)? })
+    }
+}
+
+#[async_trait]
+impl CacheStore for SledCacheStore {
+    async fn get(&self, domain: &str) -> Option<CategoryCache> {
+        let db = self.db.clone();
+        let domain = domain.to_string();
+        let ivec = tokio::task::spawn_blocking(move || db.get(domain))
+            .await
+            .ok()?
+            .ok()??;
+
+        let entry: CategoryCache = bincode::deserialize(&ivec).ok()?;
+        if entry.is_expired() {
+            None
+        } else {
+            Some(entry)
+        }
+    }
+
+    async fn put(&self, domain: &str, entry: CategoryCache) {
+        let domain = domain.to_string();
+        let bytes = match bincode::serialize(&entry) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Failed to serialize category cache entry for {}: {}", domain, e);
+                return;
+            }
+        };
+
+        let db = self.db.clone();
+        match tokio::task::spawn_blocking(move || db.insert(domain, bytes)).await {
+            Ok(Err(e)) => error!("Failed to persist category cache entry: {}", e),
+            Err(e) => error!("Category cache persist task panicked: {}", e),
+            Ok(Ok(_)) => {}
+        }
+    }
+
+    async fn cleanup_expired(&self) {
+        let db = self.db.clone();
+        let result = tokio::task::spawn_blocking(move || -> sled::Result<()> {
+            let expired: Vec<sled::IVec> = db
+                .iter()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|(key, value)| {
+                    let entry: CategoryCache = bincode::deserialize(&value).ok()?;
+                    entry.is_expired().then_some(key)
+                })
+                .collect();
+
+            for key in expired {
+                db.remove(key)?;
+            }
+            Ok(())
+        })
+        .await;
+
+        if let Ok(Err(e)) = result {
+            error!("Failed to clean up expired category cache entries: {}", e);
+        }
+    }
+}
+
+/// Categorization cache backend selection.
+#[derive(Debug, Clone)]
+pub enum CacheBackend {
+    /// In-memory only; verdicts are lost on restart.
+    InMemory,
+    /// `sled`-backed on-disk store at `path`.
+    Sled { path: String },
+}
+
+impl Default for CacheBackend {
+    fn default() -> Self {
+        CacheBackend::InMemory
     }
 }
 
@@ -119,6 +502,7 @@ impl CategoryCache {
 pub struct CategorizationConfig {
     pub enabled: bool,
     pub cache_ttl: Duration,
+    pub cache_backend: CacheBackend,
     pub shallalist_enabled: bool,
     pub shallalist_path: Option<String>,
     pub urlhaus_enabled: bool,
@@ -127,6 +511,24 @@ pub struct CategorizationConfig {
     pub phishtank_api: String,
     pub custom_db_enabled: bool,
     pub custom_db_path: Option<String>,
+    pub dnsbl_enabled: bool,
+    pub dnsbl_zones: Vec<ZoneConfig>,
+    /// Explicit resolver IPs to query; empty uses the system resolver config.
+    pub dnsbl_resolvers: Vec<IpAddr>,
+    pub dnsbl_timeout: Duration,
+    /// Periodically refresh the Shallalist and URLhaus feeds in the
+    /// background instead of only loading Shallalist once at startup and
+    /// hitting the URLhaus API per-URL.
+    pub feed_updater_enabled: bool,
+    pub feed_refresh_interval: Duration,
+    /// Shallalist tarball URL (`category/domains` entries, gzip-compressed tar).
+    pub shallalist_feed_url: Option<String>,
+    /// URLhaus full host/URL dump, used to seed the Bloom filter.
+    pub urlhaus_feed_url: String,
+    /// Expected number of entries in the URLhaus feed, used to size the
+    /// Bloom filter's bit array.
+    pub urlhaus_bloom_expected_items: usize,
+    pub urlhaus_bloom_false_positive_rate: f64,
 }
 
 impl Default for CategorizationConfig {
@@ -134,6 +536,7 @@ impl Default for CategorizationConfig {
         Self {
             enabled: false,
             cache_ttl: Duration::from_secs(3600),
+            cache_backend: CacheBackend::InMemory,
             shallalist_enabled: false,
             shallalist_path: None,
             urlhaus_enabled: false,
@@ -142,6 +545,16 @@ impl Default for CategorizationConfig {
             phishtank_api: "https://checkurl.phishtank.com/checkurl/".to_string(),
             custom_db_enabled: false,
             custom_db_path: None,
+            dnsbl_enabled: false,
+            dnsbl_zones: Vec::new(),
+            dnsbl_resolvers: Vec::new(),
+            dnsbl_timeout: Duration::from_secs(2),
+            feed_updater_enabled: false,
+            feed_refresh_interval: Duration::from_secs(6 * 3600),
+            shallalist_feed_url: None,
+            urlhaus_feed_url: "https://urlhaus.abuse.ch/downloads/csv_online/".to_string(),
+            urlhaus_bloom_expected_items: 1_000_000,
+            urlhaus_bloom_false_positive_rate: 0.001,
         }
     }
 }
@@ -149,25 +562,51 @@ impl Default for CategorizationConfig {
 /// Categorization engine
 pub struct CategorizationEngine {
     config: CategorizationConfig,
-    cache: Arc<RwLock<HashMap<String, CategoryCache>>>,
-    shallalist: Option<HashMap<String, HashSet<Category>>>,
+    cache: Arc<dyn CacheStore>,
+    /// Hot-swappable so the periodic feed updater can replace the whole
+    /// map atomically and in-flight `categorize()` calls never observe a
+    /// half-built map.
+    shallalist: StdRwLock<Arc<HashMap<String, HashSet<Category>>>>,
     custom_db: Option<HashMap<String, HashSet<Category>>>,
+    /// Bloom-filter membership set for the full URLhaus feed; `None` until
+    /// the first successful refresh (or always, if the updater is disabled).
+    urlhaus_bloom: StdRwLock<Arc<Option<BloomFilter>>>,
     http_client: Client,
+    dnsbl_resolver: Option<TokioAsyncResolver>,
 }
 
 impl CategorizationEngine {
     pub fn new(config: CategorizationConfig) -> Self {
         info!("Categorization engine initialized");
-        
+
+        let dnsbl_resolver = if config.dnsbl_enabled {
+            Some(Self::build_dnsbl_resolver(&config))
+        } else {
+            None
+        };
+
+        let cache: Arc<dyn CacheStore> = match &config.cache_backend {
+            CacheBackend::InMemory => Arc::new(InMemoryCacheStore::new()) as Arc<dyn CacheStore>,
+            CacheBackend::Sled { path } => match SledCacheStore::open(path) {
+                Ok(store) => Arc::new(store) as Arc<dyn CacheStore>,
+                Err(e) => {
+                    error!("Failed to open sled category cache at {}: {}, falling back to in-memory", path, e);
+                    Arc::new(InMemoryCacheStore::new()) as Arc<dyn CacheStore>
+                }
+            },
+        };
+
         let mut engine = Self {
             config,
-            cache: Arc::new(RwLock::new(HashMap::new())),
-            shallalist: None,
+            cache,
+            shallalist: StdRwLock::new(Arc::new(HashMap::new())),
             custom_db: None,
+            urlhaus_bloom: StdRwLock::new(Arc::new(None)),
             http_client: Client::builder()
                 .timeout(Duration::from_secs(5))
                 .build()
                 .expect("Failed to create HTTP client"),
+            dnsbl_resolver,
         };
 
         // Load Shallalist if enabled
@@ -193,6 +632,26 @@ impl CategorizationEngine {
         engine
     }
 
+    /// Build the resolver used for DNSBL/URIBL lookups: the system's
+    /// configured resolvers by default, or `dnsbl_resolvers` if given.
+    fn build_dnsbl_resolver(config: &CategorizationConfig) -> TokioAsyncResolver {
+        let mut opts = ResolverOpts::default();
+        opts.timeout = config.dnsbl_timeout;
+
+        if config.dnsbl_resolvers.is_empty() {
+            TokioAsyncResolver::tokio(ResolverConfig::default(), opts)
+        } else {
+            let mut resolver_config = ResolverConfig::new();
+            for ip in &config.dnsbl_resolvers {
+                resolver_config.add_name_server(NameServerConfig::new(
+                    SocketAddr::new(*ip, 53),
+                    Protocol::Udp,
+                ));
+            }
+            TokioAsyncResolver::tokio(resolver_config, opts)
+        }
+    }
+
     /// Categorize URL
     pub async fn categorize(&self, url: &str) -> CategorizationResult {
         let parsed_url = match Url::parse(url) {
@@ -231,6 +690,17 @@ impl CategorizationEngine {
             }
         }
 
+        // Check DNSBL/URIBL zones (DNS lookups, cheaper than the per-URL
+        // HTTP calls below)
+        if categories.is_empty() && self.config.dnsbl_enabled {
+            if let Some(cats) = self.check_dnsbl(&domain).await {
+                if !cats.is_empty() {
+                    categories.extend(cats);
+                    source = if source == "unknown" { "dnsbl" } else { "multiple" };
+                }
+            }
+        }
+
         // Check online services if no local match
         if categories.is_empty() {
             // Check URLhaus for malware
@@ -260,7 +730,7 @@ impl CategorizationEngine {
 
     /// Check Shallalist database
     fn check_shallalist(&self, domain: &str) -> Option<HashSet<Category>> {
-        self.shallalist.as_ref()?.get(domain).cloned()
+        self.shallalist.read().unwrap().get(domain).cloned()
     }
 
     /// Check custom database
@@ -270,6 +740,17 @@ impl CategorizationEngine {
 
     /// Check URLhaus API
     async fn check_urlhaus(&self, url: &str) -> Option<HashSet<Category>> {
+        // Once the feed updater has loaded a Bloom filter, skip the API
+        // call entirely for misses; a hit still needs confirming since the
+        // filter can false-positive.
+        let bloom = self.urlhaus_bloom.read().unwrap().clone();
+        if let Some(bloom) = bloom.as_ref() {
+            if !bloom.contains(url) {
+                return None;
+            }
+            debug!("URLhaus Bloom filter hit for {}, confirming against API", url);
+        }
+
         let response = self.http_client
             .post(&self.config.urlhaus_api)
             .form(&[("url", url)])
@@ -315,50 +796,140 @@ impl CategorizationEngine {
         None
     }
 
-    /// Load Shallalist database
-    fn load_shallalist(&mut self, path: &str) -> Result<usize, String> {
-        // Shallalist format: category/domains
-        // Example structure:
-        // adult/domains:
-        //   example.com
-        //   test.com
-        
-        let mut db = HashMap::new();
-        let categories_dir = std::path::Path::new(path);
-        
-        if !categories_dir.exists() {
-            return Err(format!("Shallalist directory not found: {}", path));
+    /// Check every configured DNSBL/URIBL zone for `domain`'s registrable
+    /// domain. Returns `None` when DNSBL is disabled, the host is an IP
+    /// literal, the domain has no registrable reduction, or every zone
+    /// timed out/SERVFAILed (treated as "unknown", not "not listed").
+    /// Returns `Some` (possibly empty) once at least one zone gave a
+    /// definitive answer, NXDOMAIN included.
+    async fn check_dnsbl(&self, domain: &str) -> Option<HashSet<Category>> {
+        let resolver = self.dnsbl_resolver.as_ref()?;
+
+        if domain.parse::<IpAddr>().is_ok() {
+            return None;
         }
 
-        // Read each category directory
-        for entry in std::fs::read_dir(categories_dir)
-            .map_err(|e| format!("Failed to read directory: {}", e))?
-        {
-            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-            let category_name = entry.file_name().to_string_lossy().to_string();
-            let category = Category::from_str(&category_name);
-            
-            let domains_file = entry.path().join("domains");
-            if domains_file.exists() {
-                let content = std::fs::read_to_string(&domains_file)
-                    .map_err(|e| format!("Failed to read domains file: {}", e))?;
-                
-                for line in content.lines() {
-                    let domain = line.trim();
-                    if !domain.is_empty() && !domain.starts_with('#') {
-                        db.entry(domain.to_string())
-                            .or_insert_with(HashSet::new)
-                            .insert(category.clone());
+        let registrable = registrable_domain(domain)?;
+        let mut categories = HashSet::new();
+        let mut any_definitive = false;
+
+        for zone in &self.config.dnsbl_zones {
+            let query = format!("{}.{}", registrable, zone.zone);
+
+            match resolver.lookup_ip(query.as_str()).await {
+                Ok(answer) => {
+                    any_definitive = true;
+                    for ip in answer.iter() {
+                        let IpAddr::V4(v4) = ip else { continue };
+                        if v4.octets()[0] != 127 {
+                            continue;
+                        }
+
+                        let marker = v4.octets()[3];
+                        let mut matched = false;
+                        for (&bit, category) in &zone.bit_categories {
+                            if marker & bit != 0 {
+                                categories.insert(category.clone());
+                                matched = true;
+                            }
+                        }
+                        if !matched {
+                            categories.insert(Category::Custom(format!("dnsbl:{}", zone.zone)));
+                        }
+
+                        if zone.fetch_reason {
+                            if let Some(reason) = self.fetch_dnsbl_reason(&query).await {
+                                debug!("DNSBL {} reason for {}: {}", zone.zone, domain, reason);
+                            }
+                        }
                     }
                 }
+                Err(e) => match e.kind() {
+                    ResolveErrorKind::NoRecordsFound { .. } => any_definitive = true,
+                    _ => warn!("DNSBL lookup for {} on {} failed: {}", query, zone.zone, e),
+                },
             }
         }
 
+        if categories.is_empty() && !any_definitive {
+            None
+        } else {
+            Some(categories)
+        }
+    }
+
+    /// Fetch the TXT record for an already-matched DNSBL query name, used
+    /// only to surface a human-readable listing reason in logs.
+    async fn fetch_dnsbl_reason(&self, query: &str) -> Option<String> {
+        let resolver = self.dnsbl_resolver.as_ref()?;
+        let txt = resolver.txt_lookup(query).await.ok()?;
+        txt.iter().next().map(|record| record.to_string())
+    }
+
+    /// Load Shallalist database from an already-extracted directory on disk
+    fn load_shallalist(&mut self, path: &str) -> Result<usize, String> {
+        let db = parse_shallalist_dir(path)?;
         let count = db.len();
-        self.shallalist = Some(db);
+        *self.shallalist.write().unwrap() = Arc::new(db);
         Ok(count)
     }
 
+    /// Run the periodic feed updater until `shutdown` is notified: refreshes
+    /// the Shallalist and URLhaus feeds over HTTP on `feed_refresh_interval`
+    /// and atomically swaps them in, so `categorize()` never observes a
+    /// half-built map.
+    pub async fn run_feed_updater(self: Arc<Self>, shutdown: Arc<Notify>) {
+        if !self.config.feed_updater_enabled {
+            return;
+        }
+
+        let mut interval = tokio::time::interval(self.config.feed_refresh_interval);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.refresh_feeds().await;
+                }
+                _ = shutdown.notified() => {
+                    info!("Feed updater shutting down");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Refresh whichever feeds are configured, logging load counts/errors
+    /// the same way the one-shot loaders do.
+    async fn refresh_feeds(&self) {
+        if let Some(url) = &self.config.shallalist_feed_url {
+            match fetch_shallalist_feed(&self.http_client, url).await {
+                Ok(db) => {
+                    let count = db.len();
+                    *self.shallalist.write().unwrap() = Arc::new(db);
+                    info!("Refreshed Shallalist feed: {} entries", count);
+                }
+                Err(e) => error!("Failed to refresh Shallalist feed: {}", e),
+            }
+        }
+
+        if self.config.urlhaus_enabled {
+            match fetch_urlhaus_bloom(
+                &self.http_client,
+                &self.config.urlhaus_feed_url,
+                self.config.urlhaus_bloom_expected_items,
+                self.config.urlhaus_bloom_false_positive_rate,
+            )
+            .await
+            {
+                Ok(bloom) => {
+                    *self.urlhaus_bloom.write().unwrap() = Arc::new(Some(bloom));
+                    info!("Refreshed URLhaus feed Bloom filter");
+                }
+                Err(e) => error!("Failed to refresh URLhaus feed: {}", e),
+            }
+        }
+    }
+
     /// Load custom database (JSON format)
     fn load_custom_db(&mut self, path: &str) -> Result<usize, String> {
         let content = std::fs::read_to_string(path)
@@ -382,21 +953,21 @@ impl CategorizationEngine {
 
     /// Get cached categories
     async fn get_cached(&self, domain: &str) -> Option<CategoryCache> {
-        let cache = self.cache.read().await;
-        cache.get(domain).filter(|c| !c.is_expired()).cloned()
+        self.cache.get(domain).await
     }
 
     /// Cache categories
     async fn cache_categories(&self, domain: &str, categories: HashSet<Category>) {
-        let mut cache = self.cache.write().await;
-        cache.insert(
-            domain.to_string(),
-            CategoryCache {
-                categories,
-                cached_at: Instant::now(),
-                ttl: self.config.cache_ttl,
-            },
-        );
+        self.cache
+            .put(
+                domain,
+                CategoryCache {
+                    categories,
+                    cached_at: SystemTime::now(),
+                    ttl: self.config.cache_ttl,
+                },
+            )
+            .await;
     }
 
     /// Create result
@@ -422,8 +993,7 @@ impl CategorizationEngine {
 
     /// Clean expired cache
     pub async fn cleanup_cache(&self) {
-        let mut cache = self.cache.write().await;
-        cache.retain(|_, entry| !entry.is_expired());
+        self.cache.cleanup_expired().await;
     }
 }
 
@@ -431,6 +1001,43 @@ impl CategorizationEngine {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("www.example.com"), Some("example.com".to_string()));
+        assert_eq!(registrable_domain("example.com"), Some("example.com".to_string()));
+        assert_eq!(registrable_domain("foo.bar.example.co.uk"), Some("example.co.uk".to_string()));
+        assert_eq!(registrable_domain("example.co.uk"), Some("example.co.uk".to_string()));
+        assert_eq!(registrable_domain("com"), None);
+    }
+
+    #[test]
+    fn test_bloom_filter_no_false_negatives() {
+        let mut bloom = BloomFilter::new(1000, 0.01);
+        for i in 0..500 {
+            bloom.insert(&format!("http://malware-{}.example/payload", i));
+        }
+
+        for i in 0..500 {
+            assert!(bloom.contains(&format!("http://malware-{}.example/payload", i)));
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter_mostly_rejects_unseen_items() {
+        let mut bloom = BloomFilter::new(1000, 0.01);
+        for i in 0..500 {
+            bloom.insert(&format!("http://malware-{}.example/payload", i));
+        }
+
+        let false_positives = (0..500)
+            .filter(|i| bloom.contains(&format!("http://clean-{}.example/page", i)))
+            .count();
+
+        // Sized for a 1% false-positive rate; allow generous slack since
+        // this is a single draw, not an average over many filters.
+        assert!(false_positives < 50, "unexpectedly high false-positive count: {}", false_positives);
+    }
+
     #[test]
     fn test_category_from_str() {
         assert_eq!(Category::from_str("adult"), Category::Adult);
@@ -0,0 +1,277 @@
+//! Domain category / blacklist database
+//!
+//! Loads domain -> category mappings from one or more files - plain
+//! domain-per-line blacklists (all entries tagged with a single category)
+//! and labeled category files (`domain,category` per line) - into a
+//! reversed-label trie. Lookups use longest-suffix matching, so
+//! `ads.example.com` inherits `example.com`'s category when there's no
+//! more specific entry. Feeds `AclRuleType::Category` rules in `AclEngine`
+//! so callers don't have to classify every domain themselves.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+use tracing::{debug, info, warn};
+
+/// One node in the reversed-label trie: each edge is a single domain label
+/// (e.g. `"com"`, `"example"`, `"ads"`), walked from the TLD inward.
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<String, TrieNode>,
+    category: Option<String>,
+}
+
+impl TrieNode {
+    fn insert(&mut self, labels: &[&str], category: String) {
+        match labels.split_first() {
+            Some((head, rest)) => {
+                self.children.entry((*head).to_string()).or_default().insert(rest, category);
+            }
+            None => self.category = Some(category),
+        }
+    }
+
+    /// Longest-suffix lookup: walk the trie following `labels` (TLD first),
+    /// remembering the deepest category seen so a more specific label wins
+    /// but a less specific one still applies if nothing deeper matches.
+    fn lookup(&self, labels: &[&str]) -> Option<&str> {
+        let mut node = self;
+        let mut best = self.category.as_deref();
+        for label in labels {
+            match node.children.get(*label) {
+                Some(child) => {
+                    node = child;
+                    if let Some(cat) = node.category.as_deref() {
+                        best = Some(cat);
+                    }
+                }
+                None => break,
+            }
+        }
+        best
+    }
+}
+
+/// A loaded file source: its path, the fixed category it was loaded with
+/// (`None` for labeled files, since those carry their own category per
+/// line), and the mtime it was last loaded at, for hot-reload detection.
+struct LoadedSource {
+    path: PathBuf,
+    category: Option<String>,
+    last_modified: SystemTime,
+}
+
+/// Domain -> category database with longest-suffix matching and periodic
+/// hot reload from disk.
+pub struct CategoryDb {
+    trie: RwLock<TrieNode>,
+    sources: Vec<LoadedSource>,
+}
+
+impl CategoryDb {
+    pub fn new() -> Self {
+        Self {
+            trie: RwLock::new(TrieNode::default()),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Load a plain domain-per-line blacklist, tagging every entry with
+    /// `category` (e.g. `"ads"`, `"malware"`). Blank lines and lines
+    /// starting with `#` are skipped. Returns the number of entries loaded.
+    pub fn load_blacklist(&mut self, path: impl AsRef<Path>, category: &str) -> std::io::Result<usize> {
+        let path = path.as_ref().to_path_buf();
+        let count = self.merge_file(&path, Some(category))?;
+        self.track_source(path, Some(category.to_string()));
+        Ok(count)
+    }
+
+    /// Load a labeled category file where each line is `domain,category`
+    /// (or `domain<whitespace>category`). Blank lines and lines starting
+    /// with `#` are skipped. Returns the number of entries loaded.
+    pub fn load_labeled(&mut self, path: impl AsRef<Path>) -> std::io::Result<usize> {
+        let path = path.as_ref().to_path_buf();
+        let count = self.merge_file(&path, None)?;
+        self.track_source(path, None);
+        Ok(count)
+    }
+
+    fn track_source(&mut self, path: PathBuf, category: Option<String>) {
+        let last_modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+
+        // Replace any previous record for this path rather than accumulate
+        // duplicates across repeated loads of the same file.
+        self.sources.retain(|s| s.path != path);
+        self.sources.push(LoadedSource { path, category, last_modified });
+    }
+
+    /// Parse `path` and merge its entries into the trie. `fixed_category`
+    /// tags every line when loading a plain blacklist; `None` means each
+    /// line carries its own `domain,category` pair.
+    fn merge_file(&self, path: &Path, fixed_category: Option<&str>) -> std::io::Result<usize> {
+        let contents = fs::read_to_string(path)?;
+        let mut trie = self.trie.write().unwrap();
+        let mut count = 0;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (domain, category) = match fixed_category {
+                Some(cat) => (line, cat.to_string()),
+                None => {
+                    let mut parts = line.splitn(2, |c: char| c == ',' || c.is_whitespace());
+                    let Some(domain) = parts.next() else { continue };
+                    let Some(category) = parts.next().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()) else {
+                        continue;
+                    };
+                    (domain, category)
+                }
+            };
+
+            let labels: Vec<&str> = domain.rsplit('.').collect();
+            if labels.is_empty() {
+                continue;
+            }
+
+            trie.insert(&labels, category);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Insert or overwrite a single domain's category directly, without
+    /// going through a file - useful for programmatic sources (an API, a
+    /// database row) alongside the file-backed loaders.
+    pub fn insert(&mut self, domain: &str, category: &str) {
+        let labels: Vec<&str> = domain.rsplit('.').collect();
+        if labels.is_empty() {
+            return;
+        }
+        self.trie.write().unwrap().insert(&labels, category.to_string());
+    }
+
+    /// Look up the category for `domain`, inheriting from the longest
+    /// registered suffix.
+    pub fn categorize(&self, domain: &str) -> Option<String> {
+        let labels: Vec<&str> = domain.rsplit('.').collect();
+        self.trie.read().unwrap().lookup(&labels).map(String::from)
+    }
+
+    /// If any source file's mtime has changed since it was loaded, re-read
+    /// every source file and rebuild the whole trie from scratch so domains
+    /// removed from a source file are also dropped, rather than lingering.
+    /// Entries added through `insert` are dropped by the rebuild too. Intended
+    /// to be called periodically from a background task.
+    pub fn reload_if_changed(&mut self) {
+        let any_changed = self.sources.iter().any(|source| {
+            fs::metadata(&source.path)
+                .and_then(|m| m.modified())
+                .map(|mtime| mtime > source.last_modified)
+                .unwrap_or(false)
+        });
+
+        if !any_changed {
+            return;
+        }
+
+        info!("Category source file(s) changed, rebuilding category trie");
+        *self.trie.write().unwrap() = TrieNode::default();
+
+        let sources = std::mem::take(&mut self.sources);
+        for source in sources {
+            match self.merge_file(&source.path, source.category.as_deref()) {
+                Ok(count) => {
+                    debug!("Reloaded {} entries from {}", count, source.path.display());
+                    self.track_source(source.path, source.category);
+                }
+                Err(e) => warn!("Failed to reload category source {}: {}", source.path.display(), e),
+            }
+        }
+    }
+}
+
+impl Default for CategoryDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Path to a fresh scratch file under the system temp dir, unique per
+    /// call so parallel tests don't collide.
+    fn temp_path(name: &str) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("category_db_test_{}_{}_{}", std::process::id(), n, name))
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        let mut file = fs::File::create(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_blacklist_exact_and_suffix_inheritance() {
+        let path = temp_path("blacklist.txt");
+        write_file(&path, "example.com\n# a comment\n\nbad-site.net\n");
+
+        let mut db = CategoryDb::new();
+        db.load_blacklist(&path, "ads").unwrap();
+
+        assert_eq!(db.categorize("example.com").as_deref(), Some("ads"));
+        assert_eq!(db.categorize("ads.example.com").as_deref(), Some("ads"));
+        assert_eq!(db.categorize("bad-site.net").as_deref(), Some("ads"));
+        assert_eq!(db.categorize("unrelated.com"), None);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_labeled_file_and_longest_suffix_wins() {
+        let path = temp_path("labeled.txt");
+        write_file(&path, "example.com,news\nads.example.com,adv\n");
+
+        let mut db = CategoryDb::new();
+        db.load_labeled(&path).unwrap();
+
+        assert_eq!(db.categorize("example.com").as_deref(), Some("news"));
+        assert_eq!(db.categorize("ads.example.com").as_deref(), Some("adv"));
+        assert_eq!(db.categorize("other.ads.example.com").as_deref(), Some("adv"));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_reload_drops_removed_entries() {
+        let path = temp_path("reload.txt");
+        write_file(&path, "example.com\n");
+
+        let mut db = CategoryDb::new();
+        db.load_blacklist(&path, "ads").unwrap();
+        assert_eq!(db.categorize("example.com").as_deref(), Some("ads"));
+
+        // mtime resolution can be coarse; nudge it forward explicitly.
+        write_file(&path, "other.com\n");
+        let future = SystemTime::now() + std::time::Duration::from_secs(2);
+        fs::File::open(&path).unwrap().set_modified(future).unwrap();
+
+        db.reload_if_changed();
+        assert_eq!(db.categorize("example.com"), None);
+        assert_eq!(db.categorize("other.com").as_deref(), Some("ads"));
+
+        let _ = fs::remove_file(&path);
+    }
+}
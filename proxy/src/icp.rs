@@ -6,19 +6,99 @@
 //! about the presence of cached objects.
 
 use bytes::{Buf, BufMut, BytesMut};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
 use std::io::{Error, ErrorKind, Result};
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::UdpSocket;
-use tokio::sync::mpsc;
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
 use tokio::time::timeout;
 use tracing::{debug, error, warn};
 
+/// Default freshness window for cached ICP results before a URL is
+/// re-probed live.
+const DEFAULT_RESULT_CACHE_TTL: Duration = Duration::from_secs(600);
+
+/// Default number of URLs tracked by the result cache before the
+/// least-recently-inserted entry is evicted.
+const DEFAULT_RESULT_CACHE_CAPACITY: usize = 10_000;
+
 /// ICP protocol version
 const ICP_VERSION: u8 = 2;
 
+/// Options header bit signaling that an HMAC auth trailer follows the URL.
+const ICP_OPT_AUTH: u32 = 0x1;
+
+/// Options header bit signaling that a HIT_OBJ payload (a 2-byte length
+/// followed by that many object bytes) follows the URL.
+const ICP_OPT_OBJ: u32 = 0x2;
+
+/// Default ceiling on how large an object `IcpServer` will inline in a
+/// HIT_OBJ response before degrading to a plain HIT. Keeps responses well
+/// within a single UDP datagram.
+const DEFAULT_MAX_HIT_OBJ_SIZE: usize = 16 * 1024;
+
+/// Truncated HMAC-SHA256 tag length in the auth trailer.
+const ICP_AUTH_TAG_LEN: usize = 16;
+
+/// Auth trailer size: an 8-byte big-endian Unix timestamp plus the
+/// truncated HMAC tag.
+const ICP_AUTH_TRAILER_LEN: usize = 8 + ICP_AUTH_TAG_LEN;
+
+/// Default replay window for authenticated queries.
+const ICP_DEFAULT_MAX_SKEW: Duration = Duration::from_secs(30);
+
+/// Maximum number of attempts `IcpClient`/`IcpServer` make to bind their UDP
+/// socket (the requested address first, then fresh ephemeral ports after a
+/// collision), before giving up.
+const MAX_BIND_ATTEMPTS: u32 = 5;
+
+/// Bind a UDP socket at `bind_addr`, retrying on the same host with an
+/// OS-assigned ephemeral port (`host:0`) if the requested address is already
+/// in use, for at most `MAX_BIND_ATTEMPTS` attempts in all.
+async fn bind_with_retry(bind_addr: &str) -> Result<UdpSocket> {
+    let mut last_err = None;
+
+    for attempt in 0..MAX_BIND_ATTEMPTS {
+        let addr = if attempt == 0 {
+            bind_addr.to_string()
+        } else {
+            let host = bind_addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(bind_addr);
+            format!("{}:0", host)
+        };
+
+        match UdpSocket::bind(&addr).await {
+            Ok(socket) => return Ok(socket),
+            Err(e) if e.kind() == ErrorKind::AddrInUse && attempt + 1 < MAX_BIND_ATTEMPTS => {
+                warn!(
+                    "ICP bind to {} failed ({}), retrying on a fresh ephemeral port (attempt {}/{})",
+                    addr, e, attempt + 1, MAX_BIND_ATTEMPTS
+                );
+                last_err = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| Error::new(ErrorKind::AddrInUse, "failed to bind ICP socket")))
+}
+
+/// Constant-time byte comparison, so auth tag checks don't leak timing
+/// information about how many leading bytes matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 /// ICP message opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -29,6 +109,7 @@ pub enum IcpOpcode {
     Miss = 3,
     Error = 4,
     // Extended opcodes
+    HitObj = 23,
     Denied = 22,
 }
 
@@ -39,6 +120,7 @@ impl From<u8> for IcpOpcode {
             2 => IcpOpcode::Hit,
             3 => IcpOpcode::Miss,
             4 => IcpOpcode::Error,
+            23 => IcpOpcode::HitObj,
             22 => IcpOpcode::Denied,
             _ => IcpOpcode::Invalid,
         }
@@ -52,6 +134,7 @@ impl std::fmt::Display for IcpOpcode {
             IcpOpcode::Hit => write!(f, "HIT"),
             IcpOpcode::Miss => write!(f, "MISS"),
             IcpOpcode::Error => write!(f, "ERROR"),
+            IcpOpcode::HitObj => write!(f, "HIT_OBJ"),
             IcpOpcode::Denied => write!(f, "DENIED"),
             IcpOpcode::Invalid => write!(f, "INVALID"),
         }
@@ -66,6 +149,14 @@ pub struct IcpMessage {
     pub request_number: u32,
     pub url: String,
     pub requester: SocketAddr,
+    /// Present when the message carried an auth trailer (`ICP_OPT_AUTH` set
+    /// in the options header word): the embedded Unix timestamp and
+    /// truncated HMAC tag, for `verify_auth` to check.
+    auth: Option<(u64, [u8; ICP_AUTH_TAG_LEN])>,
+    /// The cached object body, present on a HIT_OBJ response
+    /// (`ICP_OPT_OBJ` set in the options header word) so the requester can
+    /// populate its own cache without a follow-up fetch.
+    pub payload: Option<Vec<u8>>,
 }
 
 impl IcpMessage {
@@ -77,6 +168,8 @@ impl IcpMessage {
             request_number,
             url,
             requester,
+            auth: None,
+            payload: None,
         }
     }
 
@@ -88,6 +181,22 @@ impl IcpMessage {
             request_number,
             url: String::new(),
             requester,
+            auth: None,
+            payload: None,
+        }
+    }
+
+    /// Create an ICP HIT_OBJ response, carrying the cached object body
+    /// inline so the requester can skip a follow-up fetch.
+    pub fn hit_obj(request_number: u32, requester: SocketAddr, object: Vec<u8>) -> Self {
+        Self {
+            opcode: IcpOpcode::HitObj,
+            version: ICP_VERSION,
+            request_number,
+            url: String::new(),
+            requester,
+            auth: None,
+            payload: Some(object),
         }
     }
 
@@ -99,19 +208,50 @@ impl IcpMessage {
             request_number,
             url: String::new(),
             requester,
+            auth: None,
+            payload: None,
+        }
+    }
+
+    /// Create an ICP denied response, sent instead of MISS/HIT when a query
+    /// fails shared-secret authentication.
+    pub fn denied(request_number: u32, requester: SocketAddr) -> Self {
+        Self {
+            opcode: IcpOpcode::Denied,
+            version: ICP_VERSION,
+            request_number,
+            url: String::new(),
+            requester,
+            auth: None,
+            payload: None,
         }
     }
 
-    /// Encode message to bytes
-    pub fn encode(&self) -> Result<Vec<u8>> {
+    /// Encode message to bytes. For a HIT_OBJ response carrying a payload,
+    /// appends a 2-byte big-endian object length followed by the object
+    /// bytes right after the URL section, and sets `ICP_OPT_OBJ` in the
+    /// options header word. When `secret` is set, appends an auth trailer
+    /// after that: the current Unix time (8 bytes, big-endian) followed by
+    /// `HMAC-SHA256(secret, time_bytes || url_bytes)` truncated to 16 bytes,
+    /// and sets `ICP_OPT_AUTH` in the options header word to signal its
+    /// presence. Omitting `secret` produces the plain, unauthenticated wire
+    /// format.
+    pub fn encode(&self, secret: Option<&[u8]>) -> Result<Vec<u8>> {
         let mut buf = BytesMut::with_capacity(1024);
 
+        let object = matches!(self.opcode, IcpOpcode::HitObj).then_some(()).and(self.payload.as_deref());
+
+        let mut options = if secret.is_some() { ICP_OPT_AUTH } else { 0 };
+        if object.is_some() {
+            options |= ICP_OPT_OBJ;
+        }
+
         // Header (20 bytes)
         buf.put_u8(self.opcode as u8);
         buf.put_u8(self.version);
         buf.put_u16(self.url.len() as u16);
         buf.put_u32(self.request_number);
-        buf.put_u32(0); // Options (unused)
+        buf.put_u32(options);
         buf.put_u32(0); // Option data (unused)
         buf.put_u32(0); // Sender host (unused)
 
@@ -121,6 +261,24 @@ impl IcpMessage {
             buf.put_u8(0); // Null terminator
         }
 
+        if let Some(object) = object {
+            buf.put_u16(object.len() as u16);
+            buf.put(object);
+        }
+
+        if let Some(secret) = secret {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let time_bytes = now.to_be_bytes();
+
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+            mac.update(&time_bytes);
+            mac.update(self.url.as_bytes());
+            let tag = mac.finalize().into_bytes();
+
+            buf.put(&time_bytes[..]);
+            buf.put(&tag[..ICP_AUTH_TAG_LEN]);
+        }
+
         Ok(buf.to_vec())
     }
 
@@ -134,7 +292,7 @@ impl IcpMessage {
         let version = data.get_u8();
         let url_len = data.get_u16() as usize;
         let request_number = data.get_u32();
-        let _ = data.get_u32(); // options
+        let options = data.get_u32();
         let _ = data.get_u32(); // option_data
         let _ = data.get_u32(); // sender_host
 
@@ -144,21 +302,69 @@ impl IcpMessage {
 
         let url = if url_len > 0 && data.len() >= url_len {
             let url_bytes = &data[..url_len];
-            String::from_utf8_lossy(url_bytes)
+            let parsed = String::from_utf8_lossy(url_bytes)
                 .trim_end_matches('\0')
-                .to_string()
+                .to_string();
+            // Skip past the URL and its null terminator so `data` lines up
+            // with the auth trailer, if any.
+            data.advance((url_len + 1).min(data.len()));
+            parsed
         } else {
             String::new()
         };
 
+        let payload = if options & ICP_OPT_OBJ != 0 && data.len() >= 2 {
+            let obj_len = data.get_u16() as usize;
+            if data.len() >= obj_len {
+                let object = data[..obj_len].to_vec();
+                data.advance(obj_len);
+                Some(object)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let auth = if options & ICP_OPT_AUTH != 0 && data.len() >= ICP_AUTH_TRAILER_LEN {
+            let mut time_bytes = [0u8; 8];
+            time_bytes.copy_from_slice(&data[..8]);
+            let mut tag = [0u8; ICP_AUTH_TAG_LEN];
+            tag.copy_from_slice(&data[8..ICP_AUTH_TRAILER_LEN]);
+            Some((u64::from_be_bytes(time_bytes), tag))
+        } else {
+            None
+        };
+
         Ok(Self {
             opcode,
             version,
             request_number,
             url,
             requester: sender,
+            auth,
+            payload,
         })
     }
+
+    /// Verify this message's auth trailer against `secret`, requiring the
+    /// embedded timestamp to be within `max_skew` of now to bound replay.
+    /// Returns `false` if the message carries no trailer at all.
+    pub fn verify_auth(&self, secret: &[u8], max_skew: Duration) -> bool {
+        let Some((time, tag)) = self.auth else { return false };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.abs_diff(time) > max_skew.as_secs() {
+            return false;
+        }
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+        mac.update(&time.to_be_bytes());
+        mac.update(self.url.as_bytes());
+        let expected = mac.finalize().into_bytes();
+
+        constant_time_eq(&expected[..ICP_AUTH_TAG_LEN], &tag)
+    }
 }
 
 /// ICP query result
@@ -167,6 +373,109 @@ pub struct IcpResult {
     pub peer: SocketAddr,
     pub response: IcpOpcode,
     pub latency: Duration,
+    /// The cached object body, present when `response` was `HitObj` - the
+    /// caller can populate its local cache immediately instead of issuing a
+    /// follow-up HTTP request.
+    pub object: Option<Vec<u8>>,
+}
+
+/// A CARP-selectable peer: its ICP query address, a stable identity string
+/// hashed into its score (e.g. `host:port`, independent of transient
+/// connection state), and a weight controlling how large a share of the URL
+/// space it should receive relative to its siblings.
+#[derive(Debug, Clone)]
+pub struct CarpPeer {
+    pub addr: SocketAddr,
+    pub identity: String,
+    pub weight: f64,
+}
+
+/// Hash a string down to 32 bits using the standard library's (SipHash-based)
+/// `DefaultHasher`.
+fn hash32(value: &str) -> u32 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    (hasher.finish() & 0xFFFF_FFFF) as u32
+}
+
+/// Fold two 32-bit hashes together with an add-and-rotate step: the add
+/// mixes the two input distributions, and the rotate keeps the low bits of
+/// one hash from dominating the combined score.
+fn combined_hash(a: u32, b: u32) -> u32 {
+    a.wrapping_add(b).rotate_left(13)
+}
+
+/// CARP score for a candidate identified by `identity` and `weight` against
+/// a request `key`: `combined_hash(hash(key), hash(identity)) * weight`.
+/// Exposed so `selection.rs`'s `CarpStrategy` can rank ordinary cache peers
+/// by the same formula `IcpClient::rank_parents` uses for ICP parents.
+pub(crate) fn carp_score(key: &str, identity: &str, weight: f64) -> f64 {
+    combined_hash(hash32(key), hash32(identity)) as f64 * weight.max(0.0001)
+}
+
+struct CachedHit {
+    peer: SocketAddr,
+    inserted_at: Instant,
+}
+
+struct PeerResultCacheInner {
+    entries: HashMap<String, CachedHit>,
+    /// Insertion order, oldest first, for capacity-bounded eviction.
+    order: VecDeque<String>,
+}
+
+/// Time-bounded LRU cache of which peer most recently answered HIT for a
+/// given URL, shared (via `Arc`) across every `Clone` of an `IcpClient` so
+/// sibling tasks spawned by `query_peers` benefit from each other's
+/// lookups instead of each re-probing the network.
+struct PeerResultCache {
+    ttl: Duration,
+    capacity: usize,
+    inner: Mutex<PeerResultCacheInner>,
+}
+
+impl PeerResultCache {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            inner: Mutex::new(PeerResultCacheInner {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Return the cached HIT peer for `url` if an entry exists and hasn't
+    /// expired, evicting it in the process if it has.
+    fn get(&self, url: &str) -> Option<SocketAddr> {
+        let mut inner = self.inner.lock().unwrap();
+        let fresh = inner.entries.get(url).map(|hit| hit.inserted_at.elapsed() < self.ttl);
+
+        match fresh {
+            Some(true) => inner.entries.get(url).map(|hit| hit.peer),
+            Some(false) => {
+                inner.entries.remove(url);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Record that `peer` answered HIT for `url`, refreshing its position
+    /// at the back of the eviction order and evicting the oldest entry if
+    /// `capacity` is now exceeded.
+    fn insert(&self, url: String, peer: SocketAddr) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.order.retain(|queued| queued != &url);
+        inner.entries.insert(url.clone(), CachedHit { peer, inserted_at: Instant::now() });
+        inner.order.push_back(url);
+
+        while inner.entries.len() > self.capacity {
+            let Some(oldest) = inner.order.pop_front() else { break };
+            inner.entries.remove(&oldest);
+        }
+    }
 }
 
 /// ICP client for querying cache peers
@@ -174,21 +483,39 @@ pub struct IcpClient {
     socket: Arc<UdpSocket>,
     request_counter: AtomicU32,
     local_addr: SocketAddr,
+    shared_secret: Option<Arc<[u8]>>,
+    result_cache: Arc<PeerResultCache>,
 }
 
 impl IcpClient {
-    /// Create a new ICP client
+    /// Create a new ICP client with the default result-cache TTL
+    /// (`DEFAULT_RESULT_CACHE_TTL`) and capacity (`DEFAULT_RESULT_CACHE_CAPACITY`).
     pub async fn new(bind_addr: &str) -> Result<Self> {
-        let socket = UdpSocket::bind(bind_addr).await?;
+        Self::with_cache_config(bind_addr, DEFAULT_RESULT_CACHE_TTL, DEFAULT_RESULT_CACHE_CAPACITY).await
+    }
+
+    /// Create a new ICP client, configuring how long a peer's HIT response
+    /// for a URL is trusted (`cache_ttl`) and how many distinct URLs the
+    /// result cache tracks (`cache_capacity`) before evicting the oldest.
+    pub async fn with_cache_config(bind_addr: &str, cache_ttl: Duration, cache_capacity: usize) -> Result<Self> {
+        let socket = bind_with_retry(bind_addr).await?;
         let local_addr = socket.local_addr()?;
-        
+
         Ok(Self {
             socket: Arc::new(socket),
             request_counter: AtomicU32::new(1),
             local_addr,
+            shared_secret: None,
+            result_cache: Arc::new(PeerResultCache::new(cache_ttl, cache_capacity)),
         })
     }
 
+    /// Configure a shared secret so outgoing queries carry an HMAC auth
+    /// trailer. Peers without the matching secret will respond DENIED.
+    pub fn set_shared_secret(&mut self, secret: impl Into<Vec<u8>>) {
+        self.shared_secret = Some(Arc::from(secret.into().into_boxed_slice()));
+    }
+
     /// Query a single peer
     pub async fn query_peer(
         &self,
@@ -198,7 +525,7 @@ impl IcpClient {
     ) -> Result<IcpResult> {
         let request_number = self.request_counter.fetch_add(1, Ordering::Relaxed);
         let query = IcpMessage::query(request_number, url.to_string(), self.local_addr);
-        let encoded = query.encode()?;
+        let encoded = query.encode(self.shared_secret.as_deref())?;
 
         let start = std::time::Instant::now();
 
@@ -228,6 +555,7 @@ impl IcpClient {
                     peer,
                     response: response.opcode,
                     latency,
+                    object: response.payload,
                 })
             }
             Ok(Err(e)) => Err(e),
@@ -235,13 +563,28 @@ impl IcpClient {
         }
     }
 
-    /// Query multiple peers in parallel
+    /// Query multiple peers in parallel, first consulting the shared
+    /// result cache so a URL that was HIT moments ago (by this client or
+    /// any of its clones) short-circuits straight to that peer instead of
+    /// re-probing the network. Live HIT responses repopulate the cache.
     pub async fn query_peers(
         &self,
         peers: &[SocketAddr],
         url: &str,
         query_timeout: Duration,
     ) -> Vec<IcpResult> {
+        if let Some(cached_peer) = self.result_cache.get(url) {
+            if peers.contains(&cached_peer) {
+                debug!("ICP result cache hit for {} at {}", url, cached_peer);
+                return vec![IcpResult {
+                    peer: cached_peer,
+                    response: IcpOpcode::Hit,
+                    latency: Duration::ZERO,
+                    object: None,
+                }];
+            }
+        }
+
         let mut tasks = Vec::new();
 
         for &peer in peers {
@@ -256,6 +599,9 @@ impl IcpClient {
         let mut results = Vec::new();
         for task in tasks {
             if let Ok(Some(result)) = task.await {
+                if matches!(result.response, IcpOpcode::Hit | IcpOpcode::HitObj) {
+                    self.result_cache.insert(url.to_string(), result.peer);
+                }
                 results.push(result);
             }
         }
@@ -263,6 +609,51 @@ impl IcpClient {
         results
     }
 
+    /// Rank `peers` for `url` in descending CARP score order:
+    /// `combined_hash(hash(url), hash(peer.identity)) * peer.weight`. Scores
+    /// only depend on the URL and each peer's own identity/weight, so
+    /// adding or removing one peer reshuffles only ~1/N of the URL space
+    /// rather than the whole mapping.
+    fn rank_parents(url: &str, peers: &[CarpPeer]) -> Vec<CarpPeer> {
+        let mut scored: Vec<(f64, CarpPeer)> = peers
+            .iter()
+            .map(|peer| (carp_score(url, &peer.identity, peer.weight), peer.clone()))
+            .collect();
+
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        scored.into_iter().map(|(_, peer)| peer).collect()
+    }
+
+    /// Deterministically select the CARP parent for `url` among `peers`:
+    /// the single peer that should hold the canonical cached copy.
+    pub fn select_parent(&self, url: &str, peers: &[CarpPeer]) -> Option<SocketAddr> {
+        Self::rank_parents(url, peers).first().map(|p| p.addr)
+    }
+
+    /// CARP-select the canonical parent for `url` and ICP-probe only that
+    /// peer, falling back to the next-highest-scoring peer on MISS or
+    /// timeout, instead of broadcasting the query to every configured peer.
+    /// Returns the peer that answered HIT, if any.
+    pub async fn query_best(
+        &self,
+        url: &str,
+        peers: &[CarpPeer],
+        query_timeout: Duration,
+    ) -> Option<SocketAddr> {
+        for peer in Self::rank_parents(url, peers) {
+            match self.query_peer(peer.addr, url, query_timeout).await {
+                Ok(result) if matches!(result.response, IcpOpcode::Hit | IcpOpcode::HitObj) => return Some(peer.addr),
+                Ok(result) => {
+                    debug!("CARP parent {} responded {} for {}", peer.addr, result.response, url);
+                }
+                Err(e) => {
+                    warn!("CARP probe of {} failed: {}", peer.addr, e);
+                }
+            }
+        }
+        None
+    }
+
     /// Find first peer with a HIT
     pub async fn find_hit(
         &self,
@@ -274,7 +665,7 @@ impl IcpClient {
         
         results
             .into_iter()
-            .find(|r| r.response == IcpOpcode::Hit)
+            .find(|r| matches!(r.response, IcpOpcode::Hit | IcpOpcode::HitObj))
             .map(|r| r.peer)
     }
 }
@@ -287,6 +678,8 @@ impl Clone for IcpClient {
                 self.request_counter.load(Ordering::Relaxed)
             ),
             local_addr: self.local_addr,
+            shared_secret: self.shared_secret.clone(),
+            result_cache: self.result_cache.clone(),
         }
     }
 }
@@ -294,50 +687,93 @@ impl Clone for IcpClient {
 /// ICP server for responding to cache queries
 pub struct IcpServer {
     socket: Arc<UdpSocket>,
-    query_handler: Arc<dyn Fn(&str) -> bool + Send + Sync>,
+    query_handler: Arc<dyn Fn(&str) -> Option<Vec<u8>> + Send + Sync>,
+    shared_secret: Option<Arc<[u8]>>,
+    max_skew: Duration,
+    max_object_size: usize,
 }
 
 impl IcpServer {
-    /// Create a new ICP server
+    /// Create a new ICP server. `query_handler` returns `None` for a miss or
+    /// `Some(object)` for a hit; objects no larger than `max_object_size`
+    /// (see `set_max_object_size`, default `DEFAULT_MAX_HIT_OBJ_SIZE`) are
+    /// returned inline via HIT_OBJ, larger ones degrade to a plain HIT.
     pub async fn new<F>(
         bind_addr: &str,
         query_handler: F,
     ) -> Result<Self>
     where
-        F: Fn(&str) -> bool + Send + Sync + 'static,
+        F: Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static,
     {
-        let socket = UdpSocket::bind(bind_addr).await?;
+        let socket = bind_with_retry(bind_addr).await?;
         let local_addr = socket.local_addr()?;
-        
+
         debug!("ICP server listening on {}", local_addr);
 
         Ok(Self {
             socket: Arc::new(socket),
             query_handler: Arc::new(query_handler),
+            shared_secret: None,
+            max_skew: ICP_DEFAULT_MAX_SKEW,
+            max_object_size: DEFAULT_MAX_HIT_OBJ_SIZE,
         })
     }
 
-    /// Start serving ICP queries
-    pub async fn serve(self: Arc<Self>) {
+    /// Require a shared secret on incoming queries, answering DENIED to any
+    /// query that doesn't authenticate within `max_skew` of the current
+    /// time. Leaving this unset keeps the server fully unauthenticated.
+    pub fn set_shared_secret(&mut self, secret: impl Into<Vec<u8>>, max_skew: Duration) {
+        self.shared_secret = Some(Arc::from(secret.into().into_boxed_slice()));
+        self.max_skew = max_skew;
+    }
+
+    /// The address this server ended up bound to, e.g. to tell peers where
+    /// to send ICP queries when `bind_addr` used an OS-assigned port.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Configure the largest object `query_handler` can return inline via
+    /// HIT_OBJ before a hit degrades to a plain HIT instead.
+    pub fn set_max_object_size(&mut self, max_bytes: usize) {
+        self.max_object_size = max_bytes;
+    }
+
+    /// Start serving ICP queries until `shutdown` is notified. Once
+    /// notified, stops accepting new datagrams and awaits every in-flight
+    /// query handler before returning, so a SIGTERM can't cut off a response
+    /// that was already being built.
+    pub async fn serve(self: Arc<Self>, shutdown: Arc<Notify>) {
         let mut buf = vec![0u8; 1024];
+        let mut in_flight = JoinSet::new();
 
         loop {
-            match self.socket.recv_from(&mut buf).await {
-                Ok((len, addr)) => {
-                    let data = buf[..len].to_vec();
-                    let server = self.clone();
-                    
-                    tokio::spawn(async move {
-                        if let Err(e) = server.handle_query(&data, addr).await {
-                            error!("ICP query handling error: {}", e);
+            tokio::select! {
+                received = self.socket.recv_from(&mut buf) => {
+                    match received {
+                        Ok((len, addr)) => {
+                            let data = buf[..len].to_vec();
+                            let server = self.clone();
+
+                            in_flight.spawn(async move {
+                                if let Err(e) = server.handle_query(&data, addr).await {
+                                    error!("ICP query handling error: {}", e);
+                                }
+                            });
                         }
-                    });
+                        Err(e) => {
+                            error!("ICP socket error: {}", e);
+                        }
+                    }
                 }
-                Err(e) => {
-                    error!("ICP socket error: {}", e);
+                _ = shutdown.notified() => {
+                    debug!("ICP server shutting down, waiting for in-flight queries");
+                    break;
                 }
             }
         }
+
+        while in_flight.join_next().await.is_some() {}
     }
 
     async fn handle_query(&self, data: &[u8], sender: SocketAddr) -> Result<()> {
@@ -349,16 +785,28 @@ impl IcpServer {
 
         debug!("ICP query from {}: {}", sender, query.url);
 
+        if let Some(secret) = &self.shared_secret {
+            if !query.verify_auth(secret, self.max_skew) {
+                warn!("ICP query from {} failed authentication", sender);
+                let denied = IcpMessage::denied(query.request_number, sender);
+                let encoded = denied.encode(self.shared_secret.as_deref())?;
+                self.socket.send_to(&encoded, sender).await?;
+                return Ok(());
+            }
+        }
+
         // Check if we have the object in cache
-        let has_object = (self.query_handler)(&query.url);
+        let object = (self.query_handler)(&query.url);
 
-        let response = if has_object {
-            IcpMessage::hit(query.request_number, sender)
-        } else {
-            IcpMessage::miss(query.request_number, sender)
+        let response = match object {
+            Some(object) if object.len() <= self.max_object_size => {
+                IcpMessage::hit_obj(query.request_number, sender, object)
+            }
+            Some(_) => IcpMessage::hit(query.request_number, sender),
+            None => IcpMessage::miss(query.request_number, sender),
         };
 
-        let encoded = response.encode()?;
+        let encoded = response.encode(self.shared_secret.as_deref())?;
         self.socket.send_to(&encoded, sender).await?;
 
         debug!("ICP response sent to {}: {}", sender, response.opcode);
@@ -378,7 +826,7 @@ mod tests {
             "127.0.0.1:3130".parse().unwrap(),
         );
 
-        let encoded = msg.encode().unwrap();
+        let encoded = msg.encode(None).unwrap();
         assert!(encoded.len() > 20);
         
         let decoded = IcpMessage::decode(
@@ -394,7 +842,7 @@ mod tests {
     #[test]
     fn test_hit_miss_encoding() {
         let hit = IcpMessage::hit(999, "127.0.0.1:3130".parse().unwrap());
-        let encoded = hit.encode().unwrap();
+        let encoded = hit.encode(None).unwrap();
         let decoded = IcpMessage::decode(
             &encoded,
             "127.0.0.1:3130".parse().unwrap()
@@ -403,21 +851,80 @@ mod tests {
         assert_eq!(decoded.request_number, 999);
     }
 
+    #[tokio::test]
+    async fn test_carp_selection_is_deterministic_and_balanced() {
+        let client = IcpClient::new("127.0.0.1:0").await.unwrap();
+
+        let peers: Vec<CarpPeer> = (0..4)
+            .map(|i| CarpPeer {
+                addr: format!("127.0.0.1:{}", 4000 + i).parse().unwrap(),
+                identity: format!("peer-{}", i),
+                weight: 1.0,
+            })
+            .collect();
+
+        // Selecting the same URL twice always yields the same parent.
+        let first = client.select_parent("http://example.com/a", &peers);
+        let second = client.select_parent("http://example.com/a", &peers);
+        assert_eq!(first, second);
+        assert!(first.is_some());
+
+        // Across many distinct URLs, more than one peer should end up
+        // selected - i.e. the hash actually spreads load rather than
+        // collapsing onto a single peer.
+        let mut chosen = std::collections::HashSet::new();
+        for i in 0..200 {
+            let url = format!("http://example.com/item/{}", i);
+            chosen.insert(client.select_parent(&url, &peers));
+        }
+        assert!(chosen.len() > 1);
+    }
+
+    #[tokio::test]
+    async fn test_carp_query_best_falls_back_on_miss() {
+        // First server always MISSes, second always HITs.
+        let miss_server = Arc::new(IcpServer::new("127.0.0.1:0", |_| None).await.unwrap());
+        let hit_server = Arc::new(IcpServer::new("127.0.0.1:0", |_| Some(Vec::new())).await.unwrap());
+        let miss_addr = miss_server.socket.local_addr().unwrap();
+        let hit_addr = hit_server.socket.local_addr().unwrap();
+
+        for server in [miss_server.clone(), hit_server.clone()] {
+            let shutdown = Arc::new(Notify::new());
+            tokio::spawn(async move { server.serve(shutdown).await; });
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let client = IcpClient::new("127.0.0.1:0").await.unwrap();
+
+        // Force the MISS server to rank first by giving it a much larger
+        // weight, so query_best must fall back to the HIT server.
+        let peers = vec![
+            CarpPeer { addr: miss_addr, identity: "miss".to_string(), weight: 1000.0 },
+            CarpPeer { addr: hit_addr, identity: "hit".to_string(), weight: 1.0 },
+        ];
+
+        let winner = client
+            .query_best("http://example.com/test", &peers, Duration::from_millis(200))
+            .await;
+        assert_eq!(winner, Some(hit_addr));
+    }
+
     #[tokio::test]
     async fn test_client_server() {
-        // Create server that always returns HIT
+        // Create server that always returns a HIT_OBJ with the object body
         let server = Arc::new(
-            IcpServer::new("127.0.0.1:0", |_url| true)
+            IcpServer::new("127.0.0.1:0", |_url| Some(b"cached body".to_vec()))
                 .await
                 .unwrap()
         );
-        
+
         let server_addr = server.socket.local_addr().unwrap();
-        
+
         // Start server
         let server_clone = server.clone();
+        let shutdown = Arc::new(Notify::new());
         tokio::spawn(async move {
-            server_clone.serve().await;
+            server_clone.serve(shutdown).await;
         });
 
         // Give server time to start
@@ -437,7 +944,185 @@ mod tests {
 
         assert!(result.is_ok());
         let result = result.unwrap();
-        assert_eq!(result.response, IcpOpcode::Hit);
+        assert_eq!(result.response, IcpOpcode::HitObj);
         assert_eq!(result.peer, server_addr);
+        assert_eq!(result.object.as_deref(), Some(&b"cached body"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_hit_obj_degrades_to_plain_hit_over_size_limit() {
+        let mut server = IcpServer::new("127.0.0.1:0", |_| Some(vec![0u8; 64])).await.unwrap();
+        server.set_max_object_size(32);
+        let server = Arc::new(server);
+        let server_addr = server.socket.local_addr().unwrap();
+
+        let shutdown = Arc::new(Notify::new());
+        tokio::spawn(async move { server.serve(shutdown).await; });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let client = IcpClient::new("127.0.0.1:0").await.unwrap();
+        let result = client
+            .query_peer(server_addr, "http://example.com/big", Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(result.response, IcpOpcode::Hit);
+        assert_eq!(result.object, None);
+    }
+
+    #[test]
+    fn test_hit_obj_roundtrip_encoding() {
+        let msg = IcpMessage::hit_obj(42, "127.0.0.1:3130".parse().unwrap(), b"payload bytes".to_vec());
+        let encoded = msg.encode(None).unwrap();
+        let decoded = IcpMessage::decode(&encoded, "127.0.0.1:3130".parse().unwrap()).unwrap();
+
+        assert_eq!(decoded.opcode, IcpOpcode::HitObj);
+        assert_eq!(decoded.payload.as_deref(), Some(&b"payload bytes"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_client_denied_by_authenticated_server() {
+        let mut server = IcpServer::new("127.0.0.1:0", |_| Some(vec![0u8; 20_000])).await.unwrap();
+        server.set_shared_secret(b"top-secret".to_vec(), Duration::from_secs(30));
+        let server = Arc::new(server);
+        let server_addr = server.socket.local_addr().unwrap();
+
+        let shutdown = Arc::new(Notify::new());
+        tokio::spawn(async move { server.serve(shutdown).await; });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let client = IcpClient::new("127.0.0.1:0").await.unwrap();
+        let result = client
+            .query_peer(server_addr, "http://example.com/test", Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(result.response, IcpOpcode::Denied);
+    }
+
+    #[tokio::test]
+    async fn test_matching_shared_secret_authenticates() {
+        let mut server = IcpServer::new("127.0.0.1:0", |_| Some(vec![0u8; 20_000])).await.unwrap();
+        server.set_shared_secret(b"top-secret".to_vec(), Duration::from_secs(30));
+        let server = Arc::new(server);
+        let server_addr = server.socket.local_addr().unwrap();
+
+        let shutdown = Arc::new(Notify::new());
+        tokio::spawn(async move { server.serve(shutdown).await; });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut client = IcpClient::new("127.0.0.1:0").await.unwrap();
+        client.set_shared_secret(b"top-secret".to_vec());
+        let result = client
+            .query_peer(server_addr, "http://example.com/test", Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(result.response, IcpOpcode::Hit);
+    }
+
+    #[test]
+    fn test_peer_result_cache_expires_entries() {
+        let cache = PeerResultCache::new(Duration::from_millis(10), 10);
+        let addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+
+        cache.insert("http://example.com/a".to_string(), addr);
+        assert_eq!(cache.get("http://example.com/a"), Some(addr));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get("http://example.com/a"), None);
+    }
+
+    #[test]
+    fn test_peer_result_cache_evicts_oldest_over_capacity() {
+        let cache = PeerResultCache::new(Duration::from_secs(600), 2);
+        let a: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:4002".parse().unwrap();
+        let c: SocketAddr = "127.0.0.1:4003".parse().unwrap();
+
+        cache.insert("u1".to_string(), a);
+        cache.insert("u2".to_string(), b);
+        cache.insert("u3".to_string(), c);
+
+        assert_eq!(cache.get("u1"), None);
+        assert_eq!(cache.get("u2"), Some(b));
+        assert_eq!(cache.get("u3"), Some(c));
+    }
+
+    #[tokio::test]
+    async fn test_query_peers_short_circuits_on_cached_hit() {
+        let call_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = call_count.clone();
+        let server = Arc::new(
+            IcpServer::new("127.0.0.1:0", move |_| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                Some(vec![0u8; 20_000])
+            })
+            .await
+            .unwrap(),
+        );
+        let server_addr = server.socket.local_addr().unwrap();
+        let shutdown = Arc::new(Notify::new());
+        tokio::spawn(async move { server.serve(shutdown).await; });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let client = IcpClient::new("127.0.0.1:0").await.unwrap();
+        let peers = vec![server_addr];
+
+        let first = client
+            .query_peers(&peers, "http://example.com/cached", Duration::from_millis(100))
+            .await;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].response, IcpOpcode::Hit);
+        assert_eq!(call_count.load(Ordering::Relaxed), 1);
+
+        let second = client
+            .query_peers(&peers, "http://example.com/cached", Duration::from_millis(100))
+            .await;
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].response, IcpOpcode::Hit);
+        assert_eq!(second[0].latency, Duration::ZERO);
+        // The second lookup was served from cache, not a live query.
+        assert_eq!(call_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_verify_auth_rejects_stale_timestamp() {
+        let secret = b"top-secret";
+        let msg = IcpMessage::query(1, "http://example.com/x".to_string(), "127.0.0.1:3130".parse().unwrap());
+        let encoded = msg.encode(Some(secret)).unwrap();
+        let mut decoded = IcpMessage::decode(&encoded, "127.0.0.1:3130".parse().unwrap()).unwrap();
+
+        // Rewind the embedded timestamp well outside the skew window to
+        // simulate a replayed/stale message.
+        decoded.auth = decoded.auth.map(|(time, tag)| (time.saturating_sub(3600), tag));
+        assert!(!decoded.verify_auth(secret, Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn test_serve_returns_after_shutdown_notified() {
+        let server = Arc::new(IcpServer::new("127.0.0.1:0", |_| Some(Vec::new())).await.unwrap());
+        let shutdown = Arc::new(Notify::new());
+
+        let shutdown_clone = shutdown.clone();
+        let handle = tokio::spawn(async move { server.serve(shutdown_clone).await; });
+
+        shutdown.notify_one();
+        tokio::time::timeout(Duration::from_millis(200), handle)
+            .await
+            .expect("serve should return promptly after shutdown is notified")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_binds_fresh_port_after_collision() {
+        // Reserve a concrete port, then ask the client to bind exactly that
+        // address - it should fall back to an ephemeral port rather than
+        // failing outright.
+        let held = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let taken_addr = held.local_addr().unwrap();
+
+        let client = IcpClient::new(&taken_addr.to_string()).await.unwrap();
+        assert_ne!(client.local_addr, taken_addr);
     }
 }
@@ -1,8 +1,9 @@
 use base64::engine::general_purpose;
 use base64::Engine;  // Трейт для decode
 use bytes::Bytes;
+use hmac::{Hmac, Mac};
 use hyper::body::Incoming;
-use hyper::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use hyper::header::{HeaderName, HeaderValue, AUTHORIZATION, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Method, Request, Response, StatusCode};
@@ -14,24 +15,252 @@ use rcgen::{
 };
 use rdkafka::config::ClientConfig;
 use rdkafka::producer::{FutureProducer, FutureRecord};
+use rusqlite::OptionalExtension;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
 use serde::Serialize;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use tokio::io::copy_bidirectional;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
+use tokio_rustls::TlsAcceptor;
 use tracing::{debug, error, info, warn};
 
 type CertPair = (Bytes, Bytes);
 type CertMap = Arc<RwLock<HashMap<Arc<str>, CertPair>>>;
+type TlsConfigMap = Arc<RwLock<HashMap<Arc<str>, Arc<rustls::ServerConfig>>>>;
 type Body = http_body_util::Full<Bytes>;
 
+/// Runtime configuration for the MITM (TLS-terminating) CONNECT path
+#[derive(Clone)]
+struct MitmConfig {
+    /// Whether CONNECT requests should be TLS-terminated instead of tunneled
+    enabled: bool,
+    /// Domain suffixes that always fall back to blind `copy_bidirectional`
+    excluded_domains: Vec<String>,
+}
+
+impl MitmConfig {
+    fn from_env() -> Self {
+        let enabled = std::env::var("MITM_MODE")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let excluded_domains = std::env::var("MITM_EXCLUDE_DOMAINS")
+            .ok()
+            .map(|raw| {
+                raw.split(',')
+                    .map(|s| s.trim().to_lowercase())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            enabled,
+            excluded_domains,
+        }
+    }
+
+    /// Whether the given domain should be MITM'd (vs. blindly tunneled)
+    fn should_terminate(&self, domain: &str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let domain = domain.to_lowercase();
+        !self
+            .excluded_domains
+            .iter()
+            .any(|excluded| domain == *excluded || domain.ends_with(&format!(".{}", excluded)))
+    }
+}
+
 const CACHEABLE_METHODS: &[&str] = &["GET", "HEAD"];
 const CACHEABLE_STATUS_CODES: &[u16] = &[200, 203, 204, 206, 300, 301, 404, 405, 410, 414, 501];
 
+/// How long a verified (or rejected) access token is trusted before its
+/// signature is re-checked, so a hot client hammering the proxy doesn't
+/// recompute an HMAC on every single request.
+const TOKEN_VERIFY_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Gate on proxy access via signed, time-limited tokens (the mangadex v32
+/// token shape: `subject.expiry.hmac`). Disabled when
+/// `PROXY_ACCESS_TOKEN_SECRET` is unset, so the proxy stays open by default.
+#[derive(Clone)]
+struct AccessTokenConfig {
+    secret: Option<Arc<[u8]>>,
+    clock_skew: Duration,
+    always_allowed_domains: Vec<String>,
+}
+
+impl AccessTokenConfig {
+    fn from_env() -> Self {
+        let secret = std::env::var("PROXY_ACCESS_TOKEN_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|s| Arc::from(s.into_bytes().into_boxed_slice()));
+
+        let clock_skew = Duration::from_secs(
+            std::env::var("PROXY_ACCESS_TOKEN_CLOCK_SKEW_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+        );
+
+        let always_allowed_domains = std::env::var("PROXY_ACCESS_TOKEN_ALLOWED_DOMAINS")
+            .ok()
+            .map(|raw| {
+                raw.split(',')
+                    .map(|s| s.trim().to_lowercase())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            secret,
+            clock_skew,
+            always_allowed_domains,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    fn is_always_allowed(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+        self.always_allowed_domains
+            .iter()
+            .any(|allowed| domain == *allowed || domain.ends_with(&format!(".{}", allowed)))
+    }
+}
+
+/// Compute the HMAC-SHA256 signature over `subject.expiry`, hex-encoded.
+fn sign_access_token(secret: &[u8], subject: &str, expiry: u64) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(subject.as_bytes());
+    mac.update(b".");
+    mac.update(expiry.to_string().as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Constant-time byte comparison, so signature checks don't leak timing
+/// information about how many leading bytes matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Parse a `subject.expiry.signature` token and verify it against `secret`,
+/// returning the subject if the signature and expiry (plus `clock_skew`)
+/// both check out.
+fn verify_access_token(token: &str, secret: &[u8], clock_skew: Duration) -> Option<Arc<str>> {
+    let mut parts = token.splitn(3, '.');
+    let subject = parts.next()?;
+    let expiry_str = parts.next()?;
+    let signature = parts.next()?;
+
+    let expiry: u64 = expiry_str.parse().ok()?;
+    let expected = sign_access_token(secret, subject, expiry);
+    if !constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+        return None;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    if now > expiry.saturating_add(clock_skew.as_secs()) {
+        return None;
+    }
+
+    Some(Arc::from(subject))
+}
+
+/// Enable TCP keepalive on a socket, following proxmox-backup's
+/// `set_tcp_keepalive` pattern so idle pooled/accepted connections to a
+/// half-dead peer are detected instead of hanging forever.
+fn set_tcp_keepalive(stream: &TcpStream, idle: Duration) {
+    let sock_ref = socket2::SockRef::from(stream);
+    let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+    if let Err(e) = sock_ref.set_tcp_keepalive(&keepalive) {
+        warn!("Failed to set TCP keepalive: {}", e);
+    }
+}
+
+/// Parsed `Cache-Control` directives relevant to response caching (RFC 7234).
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(value: &str) -> Self {
+        let mut cc = Self {
+            no_store: false,
+            no_cache: false,
+            private: false,
+            max_age: None,
+            s_maxage: None,
+        };
+
+        for directive in value.split(',') {
+            let mut parts = directive.trim().splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim().to_lowercase();
+            let value = parts.next().map(|v| v.trim().trim_matches('"'));
+            match name.as_str() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "private" => cc.private = true,
+                "max-age" => cc.max_age = value.and_then(|v| v.parse().ok()),
+                "s-maxage" => cc.s_maxage = value.and_then(|v| v.parse().ok()),
+                _ => {}
+            }
+        }
+
+        cc
+    }
+}
+
+/// Derive a cache entry's TTL from the response's own freshness headers:
+/// `s-maxage`, then `max-age`, then `Expires`, falling back to `default_ttl`
+/// when none are present or parseable.
+fn derive_ttl(headers: &HashMap<String, String>, default_ttl: Duration) -> Duration {
+    if let Some(cache_control) = headers.get("cache-control") {
+        let cc = CacheControl::parse(cache_control);
+        if let Some(s_maxage) = cc.s_maxage {
+            return Duration::from_secs(s_maxage);
+        }
+        if let Some(max_age) = cc.max_age {
+            return Duration::from_secs(max_age);
+        }
+    }
+
+    if let Some(expires) = headers.get("expires") {
+        if let Ok(expires_at) = httpdate::parse_http_date(expires) {
+            return expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO);
+        }
+    }
+
+    default_ttl
+}
+
 /// Кешированный HTTP ответ (оптимизирован для быстрого клонирования)
 #[derive(Clone, Debug)]
 struct CachedResponse {
@@ -40,6 +269,10 @@ struct CachedResponse {
     body: Bytes,  // Bytes уже использует Arc внутри
     cached_at: SystemTime,
     ttl: Duration,
+    /// Set when the origin sent `Cache-Control: no-cache` — the entry is
+    /// still stored (and used as a revalidation source) but is always
+    /// treated as stale rather than served directly.
+    must_revalidate: bool,
 }
 
 impl CachedResponse {
@@ -50,7 +283,19 @@ impl CachedResponse {
             .map_or(true, |age| age > self.ttl)
     }
 
-    fn to_response(&self) -> Response<Body> {
+    #[inline]
+    fn needs_revalidation(&self) -> bool {
+        self.must_revalidate || self.is_expired()
+    }
+
+    fn find_header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.to_string())
+    }
+
+    fn to_response(&self, cache_status: &'static str) -> Response<Body> {
         let mut response = Response::new(Body::new(self.body.clone()));
         *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
 
@@ -64,7 +309,10 @@ impl CachedResponse {
             }
         }
 
-        headers_mut.insert("x-cache-status", HeaderValue::from_static("HIT"));
+        headers_mut.insert(
+            "x-cache-status",
+            HeaderValue::from_static(cache_status),
+        );
         response
     }
 }
@@ -73,6 +321,7 @@ impl CachedResponse {
 #[derive(Clone)]
 struct CertCache {
     certs: CertMap,
+    tls_configs: TlsConfigMap,
     ca_cert: Arc<Certificate>,
     ca_key: Arc<KeyPair>,
 }
@@ -104,6 +353,7 @@ impl CertCache {
 
         Self {
             certs: Arc::new(RwLock::new(HashMap::new())),
+            tls_configs: Arc::new(RwLock::new(HashMap::new())),
             ca_cert,
             ca_key,
         }
@@ -139,6 +389,198 @@ impl CertCache {
         cache.insert(domain_arc, cert_pair.clone());
         Ok(cert_pair)
     }
+
+    /// Get or build a `rustls::ServerConfig` for a domain, parsing the
+    /// generated leaf cert/key once and caching the result so repeat
+    /// CONNECTs to the same host skip PEM parsing entirely.
+    async fn get_or_generate_tls_config(
+        &self,
+        domain: &str,
+    ) -> Result<Arc<rustls::ServerConfig>, Box<dyn std::error::Error>> {
+        let domain_arc: Arc<str> = domain.into();
+
+        {
+            let cache = self.tls_configs.read().await;
+            if let Some(config) = cache.get(&domain_arc) {
+                return Ok(config.clone());
+            }
+        }
+
+        let (cert_pem, key_pem) = self.get_or_generate(domain).await?;
+        let cert_chain: Vec<CertificateDer<'static>> =
+            rustls_pemfile::certs(&mut cert_pem.as_ref()).collect::<Result<_, _>>()?;
+        let private_key: PrivateKeyDer<'static> =
+            rustls_pemfile::private_key(&mut key_pem.as_ref())?
+                .ok_or("no private key found in generated cert")?;
+
+        let config = Arc::new(
+            rustls::ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(cert_chain, private_key)?,
+        );
+
+        self.tls_configs.write().await.insert(domain_arc, config.clone());
+        Ok(config)
+    }
+}
+
+/// Callback invoked for an upstream certificate chain that failed the
+/// default WebPKI validation. Receives the presented chain and the target
+/// host; returning `true` accepts the connection anyway (pinning/relaxing
+/// checks for origins behind self-signed certs).
+type UpstreamVerifyOverride = Arc<dyn Fn(&[CertificateDer<'static>], &str) -> bool + Send + Sync>;
+
+/// Server certificate verifier that defers to the system trust store and
+/// only consults the operator-supplied override callback when the default
+/// verification fails, so pinning/relaxation is opt-in per deployment.
+struct PinningServerVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    override_fn: Option<UpstreamVerifyOverride>,
+}
+
+impl std::fmt::Debug for PinningServerVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinningServerVerifier")
+            .field("has_override", &self.override_fn.is_some())
+            .finish()
+    }
+}
+
+impl ServerCertVerifier for PinningServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            Ok(verified) => Ok(verified),
+            Err(e) => {
+                let Some(override_fn) = &self.override_fn else {
+                    return Err(e);
+                };
+
+                let host = server_name_to_string(server_name);
+                let mut chain = vec![end_entity.clone().into_owned()];
+                chain.extend(intermediates.iter().map(|c| c.clone().into_owned()));
+
+                if override_fn(&chain, &host) {
+                    warn!("Upstream cert override accepted {} despite: {}", host, e);
+                    Ok(ServerCertVerified::assertion())
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+fn server_name_to_string(name: &ServerName<'_>) -> String {
+    match name {
+        ServerName::DnsName(dns) => dns.as_ref().to_string(),
+        _ => format!("{:?}", name),
+    }
+}
+
+/// Coordinates graceful shutdown: counts in-flight connection handlers and
+/// lets `main` wait (with a bounded grace period) for them to finish before
+/// flushing Kafka and exiting. Handlers are not told to wind down; they run
+/// until their connection closes or the grace period ends.
+#[derive(Clone)]
+struct Shutdown {
+    notify: Arc<Notify>,
+    triggered: Arc<std::sync::atomic::AtomicBool>,
+    active_connections: Arc<AtomicUsize>,
+}
+
+impl Shutdown {
+    fn new() -> Self {
+        Self {
+            notify: Arc::new(Notify::new()),
+            triggered: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            active_connections: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Wait until SIGINT, SIGTERM, or (on Unix) SIGQUIT is received
+    async fn wait_for_signal() {
+        #[cfg(unix)]
+        {
+            let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("failed to install SIGTERM handler");
+            let mut sigquit = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::quit())
+                .expect("failed to install SIGQUIT handler");
+
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+                _ = sigterm.recv() => info!("Received SIGTERM"),
+                _ = sigquit.recv() => info!("Received SIGQUIT"),
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Received Ctrl-C");
+        }
+    }
+
+    /// Mark shutdown as triggered and wake anything waiting on `notified()`
+    fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    fn begin_connection(&self) {
+        self.active_connections.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn end_connection(&self) {
+        self.active_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Wait for active connections to drain, up to `grace_period`
+    async fn drain(&self, grace_period: Duration) {
+        let deadline = Instant::now() + grace_period;
+        while self.active_connections.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let remaining = self.active_connections.load(Ordering::SeqCst);
+        if remaining > 0 {
+            warn!("Shutdown grace period elapsed with {} connections still active", remaining);
+        }
+    }
 }
 
 /// Событие для Kafka (оптимизировано для сериализации)
@@ -184,21 +626,340 @@ impl Default for CacheConfig {
     }
 }
 
+/// Configuration for the disk-backed second cache tier (see `DiskCache`).
+#[derive(Clone)]
+struct DiskCacheConfig {
+    enabled: bool,
+    dir: PathBuf,
+    max_bytes: u64,
+    write_through_threshold: usize,
+}
+
+impl DiskCacheConfig {
+    fn from_env() -> Self {
+        let enabled = std::env::var("DISK_CACHE_ENABLED")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(true);
+        let dir = std::env::var("DISK_CACHE_DIR")
+            .unwrap_or_else(|_| "/var/cache/bsdm-proxy".to_string())
+            .into();
+        let max_bytes = std::env::var("DISK_CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1024 * 1024 * 1024); // 1GB
+        let write_through_threshold = std::env::var("DISK_CACHE_WRITE_THROUGH_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(64 * 1024); // 64KB
+        Self {
+            enabled,
+            dir,
+            max_bytes,
+            write_through_threshold,
+        }
+    }
+}
+
+/// Second cache tier behind `http_cache`: response bodies live as files
+/// under `dir`, indexed by a SQLite table (cache key, status, headers,
+/// `cached_at`, ttl, body path, size) so a restart doesn't cold-start
+/// every origin. `quick_cache` misses fall through here before going
+/// upstream, and hits are promoted back into memory. Eviction is
+/// oldest-first by `cached_at` once `max_bytes` is exceeded.
+struct DiskCache {
+    conn: Arc<std::sync::Mutex<rusqlite::Connection>>,
+    dir: PathBuf,
+    max_bytes: u64,
+    write_through_threshold: usize,
+}
+
+impl DiskCache {
+    async fn open(config: &DiskCacheConfig) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        tokio::fs::create_dir_all(&config.dir).await?;
+        let db_path = config.dir.join("index.sqlite3");
+
+        let conn = tokio::task::spawn_blocking(move || -> rusqlite::Result<rusqlite::Connection> {
+            let conn = rusqlite::Connection::open(db_path)?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS cache_entries (
+                    cache_key TEXT PRIMARY KEY,
+                    status INTEGER NOT NULL,
+                    headers TEXT NOT NULL,
+                    body_path TEXT NOT NULL,
+                    body_size INTEGER NOT NULL,
+                    cached_at INTEGER NOT NULL,
+                    ttl_secs INTEGER NOT NULL,
+                    must_revalidate INTEGER NOT NULL DEFAULT 0
+                );",
+            )?;
+            Ok(conn)
+        })
+        .await??;
+
+        let cache = Self {
+            conn: Arc::new(std::sync::Mutex::new(conn)),
+            dir: config.dir.clone(),
+            max_bytes: config.max_bytes,
+            write_through_threshold: config.write_through_threshold,
+        };
+        cache.recover().await?;
+        Ok(cache)
+    }
+
+    /// Reload the index at startup: drop rows whose body file has gone
+    /// missing, and remove body files with no matching row (e.g. a crash
+    /// between writing the file and committing the index entry).
+    async fn recover(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.clone();
+        let dir = self.dir.clone();
+        tokio::task::spawn_blocking(move || -> rusqlite::Result<()> {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT cache_key, body_path FROM cache_entries")?;
+            let rows: Vec<(String, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .filter_map(Result::ok)
+                .collect();
+            drop(stmt);
+
+            let mut known_paths = std::collections::HashSet::new();
+            for (key, path) in &rows {
+                if std::path::Path::new(path).exists() {
+                    known_paths.insert(path.clone());
+                } else {
+                    conn.execute("DELETE FROM cache_entries WHERE cache_key = ?1", [key])?;
+                }
+            }
+
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let is_body_file = path.extension().is_some_and(|e| e == "body");
+                    if is_body_file && !known_paths.contains(&path.to_string_lossy().to_string()) {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await??;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let conn = self.conn.clone();
+        let key = key.to_string();
+        let row = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<(u16, String, String, u64, u64, bool)>> {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT status, headers, body_path, cached_at, ttl_secs, must_revalidate FROM cache_entries WHERE cache_key = ?1",
+                [&key],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+            )
+            .optional()
+        })
+        .await
+        .ok()?
+        .ok()??;
+
+        let (status, headers_json, body_path, cached_at, ttl_secs, must_revalidate) = row;
+        let cached_at = SystemTime::UNIX_EPOCH + Duration::from_secs(cached_at);
+        let ttl = Duration::from_secs(ttl_secs);
+        if SystemTime::now().duration_since(cached_at).map_or(true, |age| age > ttl) {
+            self.remove(&body_path).await;
+            return None;
+        }
+
+        let body = tokio::fs::read(&body_path).await.ok()?;
+        let headers: Vec<(String, String)> = serde_json::from_str(&headers_json).ok()?;
+        let headers: Arc<[(Arc<str>, Arc<str>)]> = headers
+            .into_iter()
+            .map(|(k, v)| (Arc::from(k.as_str()), Arc::from(v.as_str())))
+            .collect();
+
+        Some(CachedResponse {
+            status,
+            headers,
+            body: Bytes::from(body),
+            cached_at,
+            ttl,
+            must_revalidate,
+        })
+    }
+
+    async fn insert(&self, key: &str, response: &CachedResponse) {
+        let body_path = self.dir.join(format!("{}.body", key));
+        if let Err(e) = tokio::fs::write(&body_path, &response.body).await {
+            warn!("Disk cache: failed to write body for {}: {}", key, e);
+            return;
+        }
+
+        let headers: Vec<(&str, &str)> = response
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_ref(), v.as_ref()))
+            .collect();
+        let headers_json = match serde_json::to_string(&headers) {
+            Ok(j) => j,
+            Err(e) => {
+                warn!("Disk cache: failed to serialize headers for {}: {}", key, e);
+                return;
+            }
+        };
+
+        let cached_at = response
+            .cached_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let body_path_str = body_path.to_string_lossy().to_string();
+        let params = (
+            key.to_string(),
+            response.status,
+            headers_json,
+            body_path_str,
+            response.body.len() as u64,
+            cached_at,
+            response.ttl.as_secs(),
+            response.must_revalidate,
+        );
+        let conn = self.conn.clone();
+        let max_bytes = self.max_bytes;
+
+        let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<()> {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR REPLACE INTO cache_entries
+                 (cache_key, status, headers, body_path, body_size, cached_at, ttl_secs, must_revalidate)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![
+                    params.0, params.1, params.2, params.3, params.4, params.5, params.6, params.7
+                ],
+            )?;
+            evict_oldest_over_budget(&conn, max_bytes)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Disk cache: failed to index entry: {}", e),
+            Err(e) => warn!("Disk cache: insert task panicked: {}", e),
+        }
+    }
+
+    /// Drop the index row and body file for `body_path` (used when a read
+    /// finds an expired entry).
+    async fn remove(&self, body_path: &str) {
+        let conn = self.conn.clone();
+        let body_path = body_path.to_string();
+        let _ = tokio::task::spawn_blocking(move || -> rusqlite::Result<()> {
+            let conn = conn.lock().unwrap();
+            conn.execute("DELETE FROM cache_entries WHERE body_path = ?1", [&body_path])?;
+            Ok(())
+        })
+        .await;
+        let _ = std::fs::remove_file(body_path);
+    }
+}
+
+/// Evict entries oldest-first by `cached_at` until total body size is
+/// back under `max_bytes`. Called with the connection mutex already held.
+fn evict_oldest_over_budget(conn: &rusqlite::Connection, max_bytes: u64) -> rusqlite::Result<()> {
+    let total: i64 = conn.query_row("SELECT COALESCE(SUM(body_size), 0) FROM cache_entries", [], |r| r.get(0))?;
+    let mut total = total as u64;
+    if total <= max_bytes {
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare("SELECT cache_key, body_path, body_size FROM cache_entries ORDER BY cached_at ASC")?;
+    let rows: Vec<(String, String, i64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .filter_map(Result::ok)
+        .collect();
+    drop(stmt);
+
+    for (key, path, size) in rows {
+        if total <= max_bytes {
+            break;
+        }
+        conn.execute("DELETE FROM cache_entries WHERE cache_key = ?1", [&key])?;
+        let _ = std::fs::remove_file(path);
+        total = total.saturating_sub(size as u64);
+    }
+    Ok(())
+}
+
+/// Upstream request timeout and connection keepalive settings
+#[derive(Clone, Copy)]
+struct UpstreamConfig {
+    timeout: Duration,
+    tcp_keepalive: Duration,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(120),
+            tcp_keepalive: Duration::from_secs(60),
+        }
+    }
+}
+
+impl UpstreamConfig {
+    fn from_env() -> Self {
+        let default = Self::default();
+        let timeout = std::env::var("UPSTREAM_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(default.timeout);
+        let tcp_keepalive = std::env::var("UPSTREAM_KEEPALIVE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(default.tcp_keepalive);
+        Self {
+            timeout,
+            tcp_keepalive,
+        }
+    }
+}
+
+type TokenVerifyCache = Arc<RwLock<HashMap<String, (Option<Arc<str>>, Instant)>>>;
+
+/// For each base cache key, the request header names a previously cached
+/// response's `Vary` header listed — used to fold those values into a
+/// variant-specific cache key on subsequent lookups.
+type VaryMap = Arc<RwLock<HashMap<Arc<str>, Vec<String>>>>;
+
 /// Главный прокси сервис
 #[derive(Clone)]
 struct ProxyService {
     cert_cache: CertCache,
+    mitm_config: MitmConfig,
     http_cache: Arc<Cache<Arc<str>, CachedResponse>>,
     cache_config: CacheConfig,
+    disk_cache: Option<Arc<DiskCache>>,
+    upstream_config: UpstreamConfig,
+    access_token_config: AccessTokenConfig,
+    token_verify_cache: TokenVerifyCache,
+    vary_headers: VaryMap,
     kafka_producer: Option<Arc<FutureProducer>>,
-    http_client: hyper_util::client::legacy::Client<hyper_util::client::legacy::connect::HttpConnector, Body>,
+    http_client: hyper_util::client::legacy::Client<
+        hyper_rustls::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>,
+        Body,
+    >,
 }
 
 impl ProxyService {
     fn new(
         cert_cache: CertCache,
+        mitm_config: MitmConfig,
         cache_config: CacheConfig,
+        disk_cache: Option<Arc<DiskCache>>,
+        upstream_config: UpstreamConfig,
+        access_token_config: AccessTokenConfig,
         kafka_brokers: Option<String>,
+        upstream_verify_override: Option<UpstreamVerifyOverride>,
     ) -> Self {
         let kafka_producer = kafka_brokers.and_then(|brokers| {
             ClientConfig::new()
@@ -214,17 +975,57 @@ impl ProxyService {
         });
 
         let http_cache = Arc::new(Cache::new(cache_config.capacity));
-        
-        // Переиспользуемый HTTP клиент с connection pooling
+
+        // Переиспользуемый HTTP клиент с connection pooling, теперь поверх rustls
+        // чтобы https:// апстримы (включая расшифрованные MITM запросы) работали
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store.add_parsable_certificates(
+            rustls_native_certs::load_native_certs().certs,
+        );
+
+        let tls_config = rustls::ClientConfig::builder()
+            .with_root_certificates(root_store.clone())
+            .with_no_client_auth();
+
+        let tls_config = if let Some(override_fn) = upstream_verify_override {
+            let inner_verifier = WebPkiServerVerifier::builder(Arc::new(root_store))
+                .build()
+                .expect("failed to build default upstream verifier");
+            let mut config = tls_config;
+            config.dangerous().set_certificate_verifier(Arc::new(PinningServerVerifier {
+                inner: inner_verifier,
+                override_fn: Some(override_fn),
+            }));
+            config
+        } else {
+            tls_config
+        };
+
+        let mut base_connector = hyper_util::client::legacy::connect::HttpConnector::new();
+        base_connector.enforce_http(false);
+        base_connector.set_keepalive(Some(upstream_config.tcp_keepalive));
+
+        let https_connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls_config)
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(base_connector);
+
         let http_client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
             .pool_idle_timeout(Duration::from_secs(90))
             .pool_max_idle_per_host(32)
-            .build_http();
+            .build(https_connector);
 
         Self {
             cert_cache,
+            mitm_config,
             http_cache,
             cache_config,
+            disk_cache,
+            upstream_config,
+            access_token_config,
+            token_verify_cache: Arc::new(RwLock::new(HashMap::new())),
+            vary_headers: Arc::new(RwLock::new(HashMap::new())),
             kafka_producer,
             http_client,
         }
@@ -240,10 +1041,63 @@ impl ProxyService {
     }
 
     #[inline]
-    fn is_cacheable(&self, method: &str, status: u16, body_size: usize) -> bool {
-        CACHEABLE_METHODS.contains(&method)
-            && CACHEABLE_STATUS_CODES.contains(&status)
-            && body_size <= self.cache_config.max_body_size
+    fn is_cacheable(
+        &self,
+        method: &str,
+        status: u16,
+        body_size: usize,
+        headers: &HashMap<String, String>,
+    ) -> bool {
+        if !CACHEABLE_METHODS.contains(&method)
+            || !CACHEABLE_STATUS_CODES.contains(&status)
+            || body_size > self.cache_config.max_body_size
+        {
+            return false;
+        }
+
+        if let Some(cache_control) = headers.get("cache-control") {
+            let cc = CacheControl::parse(cache_control);
+            if cc.no_store || cc.private {
+                return false;
+            }
+        }
+
+        if headers.get("vary").is_some_and(|v| v.trim() == "*") {
+            return false;
+        }
+
+        true
+    }
+
+    /// Expand a method+URL base cache key into a `Vary`-aware variant key by
+    /// folding in the request header values named by a prior response's
+    /// `Vary` header for this base key. Headers absent from the current
+    /// request are treated as empty so the same variant key is produced
+    /// consistently.
+    async fn vary_aware_cache_key(&self, base_key: &Arc<str>, req: &Request<Incoming>) -> Arc<str> {
+        let vary_names = {
+            let map = self.vary_headers.read().await;
+            map.get(base_key).cloned()
+        };
+
+        let Some(vary_names) = vary_names else {
+            return base_key.clone();
+        };
+        if vary_names.is_empty() {
+            return base_key.clone();
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(base_key.as_bytes());
+        for name in &vary_names {
+            hasher.update(b"|");
+            hasher.update(name.to_lowercase().as_bytes());
+            hasher.update(b"=");
+            if let Some(value) = req.headers().get(name.as_str()).and_then(|v| v.to_str().ok()) {
+                hasher.update(value.as_bytes());
+            }
+        }
+        hex::encode(hasher.finalize()).into()
     }
 
     #[inline]
@@ -271,6 +1125,56 @@ impl ProxyService {
         (None, None)
     }
 
+    fn extract_access_token(req: &Request<Incoming>) -> Option<String> {
+        req.headers()
+            .get(PROXY_AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|t| t.to_string())
+    }
+
+    /// Verify `token` against the configured signing key, consulting
+    /// `token_verify_cache` first so the same token isn't re-hashed on
+    /// every request.
+    async fn verify_access_token(&self, token: &str) -> Option<Arc<str>> {
+        let secret = self.access_token_config.secret.as_ref()?;
+
+        if let Some((subject, cached_at)) = self.token_verify_cache.read().await.get(token) {
+            if cached_at.elapsed() < TOKEN_VERIFY_CACHE_TTL {
+                return subject.clone();
+            }
+        }
+
+        let subject = verify_access_token(token, secret, self.access_token_config.clock_skew);
+        self.token_verify_cache
+            .write()
+            .await
+            .insert(token.to_string(), (subject.clone(), Instant::now()));
+        subject
+    }
+
+    fn proxy_auth_required_response() -> Response<Body> {
+        let mut response = Response::new(Body::new(Bytes::from_static(
+            b"407 Proxy Authentication Required",
+        )));
+        *response.status_mut() = StatusCode::PROXY_AUTHENTICATION_REQUIRED;
+        response
+            .headers_mut()
+            .insert(PROXY_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        response
+    }
+
+    /// Flush any buffered Kafka records before shutdown, so `acks=0`
+    /// fire-and-forget events aren't silently dropped on exit.
+    fn flush_kafka(&self, timeout: Duration) {
+        if let Some(producer) = &self.kafka_producer {
+            match producer.flush(timeout) {
+                Ok(()) => info!("Kafka producer flushed cleanly"),
+                Err(e) => warn!("Kafka flush did not complete: {}", e),
+            }
+        }
+    }
+
     // Асинхронная отправка в Kafka без блокировки
     fn send_to_kafka_async(&self, event: CacheEvent) {
         if let Some(producer) = self.kafka_producer.clone() {
@@ -290,23 +1194,55 @@ impl ProxyService {
         }
     }
 
+    /// `preauthenticated_subject` lets a MITM'd connection thread through
+    /// the subject verified once at `CONNECT` time, instead of re-demanding
+    /// a `Proxy-Authorization` header on every decrypted inner request — a
+    /// browser that tunneled a CONNECT believes it's talking directly to the
+    /// origin and will never attach one. `None` means "check per-request as
+    /// usual", which is what plain (non-MITM) proxy requests still need.
     async fn handle_request(
         &self,
         req: Request<Incoming>,
         client_ip: String,
+        preauthenticated_subject: Option<Arc<str>>,
     ) -> Result<Response<Body>, Box<dyn std::error::Error + Send + Sync>> {
         let request_start = Instant::now();
         let method = req.method().to_string();
         let uri = req.uri().clone();
         let url = uri.to_string();
-        let (user_id, username) = Self::extract_user_info(&req);
-        let cache_key = self.generate_cache_key(&method, &url);
+        let (mut user_id, mut username) = Self::extract_user_info(&req);
+
+        if let Some(subject) = &preauthenticated_subject {
+            user_id = Some(subject.to_string());
+            username = Some(subject.to_string());
+        } else if self.access_token_config.enabled()
+            && !self.access_token_config.is_always_allowed(&Self::extract_domain(&url))
+        {
+            let subject = match Self::extract_access_token(&req) {
+                Some(token) => self.verify_access_token(&token).await,
+                None => None,
+            };
+            match subject {
+                Some(subject) => {
+                    user_id = Some(subject.to_string());
+                    username = Some(subject.to_string());
+                }
+                None => return Ok(Self::proxy_auth_required_response()),
+            }
+        }
+
+        let base_key = self.generate_cache_key(&method, &url);
+        let cache_key = self.vary_aware_cache_key(&base_key, &req).await;
+
+        // Проверка кеша. A hit that still needs revalidation is stashed
+        // rather than served, and carried into the upstream request below as
+        // a conditional GET instead of triggering a second round-trip.
+        let mut stale: Option<CachedResponse> = None;
 
-        // Проверка кеша
         if let Some(cached) = self.http_cache.get(&cache_key) {
-            if !cached.is_expired() {
+            if !cached.needs_revalidation() {
                 info!("Cache HIT: {} {}", method, url);
-                
+
                 let event = CacheEvent {
                     url: url.clone(),
                     method: method.clone(),
@@ -326,22 +1262,81 @@ impl ProxyService {
                         .map(|(_, v)| v.to_string()),
                     user_agent: None,
                 };
-                
+
                 self.send_to_kafka_async(event);
-                return Ok(cached.to_response());
+                return Ok(cached.to_response("HIT"));
             }
+            stale = Some(cached);
+        }
+
+        // Not in memory at all — fall through to the disk tier before going
+        // upstream. A stale memory entry goes straight to revalidation.
+        if stale.is_none() {
+            if let Some(disk_cache) = &self.disk_cache {
+                if let Some(cached) = disk_cache.get(&cache_key).await {
+                    if !cached.needs_revalidation() {
+                        info!("Cache HIT (disk): {} {}", method, url);
+
+                        let event = CacheEvent {
+                            url: url.clone(),
+                            method: method.clone(),
+                            status: cached.status,
+                            cache_key: cache_key.to_string(),
+                            cache_status: "HIT",
+                            timestamp: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs(),
+                            headers: HashMap::new(),
+                            user_id: user_id.clone(),
+                            username: username.clone(),
+                            client_ip: client_ip.clone(),
+                            domain: Self::extract_domain(&url),
+                            response_size: cached.body.len() as u64,
+                            request_duration_ms: request_start.elapsed().as_millis() as u64,
+                            content_type: cached.headers.iter()
+                                .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+                                .map(|(_, v)| v.to_string()),
+                            user_agent: None,
+                        };
+
+                        self.send_to_kafka_async(event);
+                        let response = cached.to_response("HIT");
+                        self.http_cache.insert(cache_key.clone(), cached);
+                        return Ok(response);
+                    }
+                    stale = Some(cached);
+                }
+            }
+        }
+
+        if stale.is_some() {
+            info!("Cache STALE, revalidating: {} {}", method, url);
+        } else {
+            info!("Cache MISS: {} {}", method, url);
         }
 
-        info!("Cache MISS: {} {}", method, url);
-        
         // Преобразование Incoming в Body для клиента
-        let (parts, body) = req.into_parts();
+        let (mut parts, body) = req.into_parts();
         let body_bytes = http_body_util::BodyExt::collect(body).await?.to_bytes();
+
+        // Attach validators from the stale entry so the single upstream
+        // request doubles as a conditional revalidation.
+        if let Some(stale) = &stale {
+            if let Some(etag) = stale.find_header("etag") {
+                if let Ok(value) = HeaderValue::from_str(&etag) {
+                    parts.headers.insert(hyper::header::IF_NONE_MATCH, value);
+                }
+            }
+            if let Some(last_modified) = stale.find_header("last-modified") {
+                if let Ok(value) = HeaderValue::from_str(&last_modified) {
+                    parts.headers.insert(hyper::header::IF_MODIFIED_SINCE, value);
+                }
+            }
+        }
+
         let req = Request::from_parts(parts, Body::new(body_bytes));
-        
+
         // Запрос к upstream с переиспользуемым клиентом
-        match self.http_client.request(req).await {
-            Ok(response) => {
+        match tokio::time::timeout(self.upstream_config.timeout, self.http_client.request(req)).await {
+            Ok(Ok(response)) => {
                 let status = response.status();
                 let headers_map: HashMap<String, String> = response
                     .headers()
@@ -349,25 +1344,95 @@ impl ProxyService {
                     .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.as_str().to_string(), v.to_string())))
                     .collect();
 
+                if status == StatusCode::NOT_MODIFIED {
+                    if let Some(mut stale) = stale {
+                        http_body_util::BodyExt::collect(response.into_body()).await.ok();
+
+                        stale.cached_at = SystemTime::now();
+                        stale.ttl = derive_ttl(&headers_map, self.cache_config.default_ttl);
+                        stale.must_revalidate = headers_map
+                            .get("cache-control")
+                            .is_some_and(|v| CacheControl::parse(v).no_cache);
+
+                        if let Some(disk_cache) = &self.disk_cache {
+                            let disk_cache = disk_cache.clone();
+                            let cache_key = cache_key.clone();
+                            let stale = stale.clone();
+                            tokio::spawn(async move {
+                                disk_cache.insert(&cache_key, &stale).await;
+                            });
+                        }
+                        self.http_cache.insert(cache_key.clone(), stale.clone());
+
+                        let event = CacheEvent {
+                            url: url.clone(),
+                            method: method.clone(),
+                            status: stale.status,
+                            cache_key: cache_key.to_string(),
+                            cache_status: "REVALIDATED",
+                            timestamp: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs(),
+                            headers: HashMap::new(),
+                            user_id,
+                            username,
+                            client_ip,
+                            domain: Self::extract_domain(&url),
+                            response_size: stale.body.len() as u64,
+                            request_duration_ms: request_start.elapsed().as_millis() as u64,
+                            content_type: stale.find_header("content-type"),
+                            user_agent: None,
+                        };
+                        self.send_to_kafka_async(event);
+                        return Ok(stale.to_response("REVALIDATED"));
+                    }
+                }
+
                 let body_bytes = http_body_util::BodyExt::collect(response.into_body())
                     .await?
                     .to_bytes();
                 let body_size = body_bytes.len();
-                
-                let cache_status = if self.is_cacheable(&method, status.as_u16(), body_size) {
+
+                let cache_status = if self.is_cacheable(&method, status.as_u16(), body_size, &headers_map) {
                     // Оптимизация: Arc для заголовков
                     let headers_arc: Arc<[(Arc<str>, Arc<str>)]> = headers_map
                         .iter()
                         .map(|(k, v)| (Arc::from(k.as_str()), Arc::from(v.as_str())))
                         .collect();
-                    
+
+                    if let Some(vary) = headers_map.get("vary") {
+                        let names: Vec<String> = vary
+                            .split(',')
+                            .map(|n| n.trim().to_string())
+                            .filter(|n| !n.is_empty())
+                            .collect();
+                        if !names.is_empty() {
+                            self.vary_headers.write().await.insert(base_key.clone(), names);
+                        }
+                    }
+
+                    let must_revalidate = headers_map
+                        .get("cache-control")
+                        .is_some_and(|v| CacheControl::parse(v).no_cache);
+
                     let cached_response = CachedResponse {
                         status: status.as_u16(),
                         headers: headers_arc,
                         body: body_bytes.clone(),
                         cached_at: SystemTime::now(),
-                        ttl: self.cache_config.default_ttl,
+                        ttl: derive_ttl(&headers_map, self.cache_config.default_ttl),
+                        must_revalidate,
                     };
+
+                    if let Some(disk_cache) = &self.disk_cache {
+                        if body_size > disk_cache.write_through_threshold {
+                            let disk_cache = disk_cache.clone();
+                            let cache_key = cache_key.clone();
+                            let cached_response = cached_response.clone();
+                            tokio::spawn(async move {
+                                disk_cache.insert(&cache_key, &cached_response).await;
+                            });
+                        }
+                    }
+
                     self.http_cache.insert(cache_key.clone(), cached_response);
                     "MISS"
                 } else {
@@ -391,7 +1456,7 @@ impl ProxyService {
                     content_type: headers_map.get("content-type").cloned(),
                     user_agent: headers_map.get("user-agent").cloned(),
                 };
-                
+
                 self.send_to_kafka_async(event);
 
                 let mut resp = Response::new(Body::new(body_bytes));
@@ -406,16 +1471,71 @@ impl ProxyService {
                 }
                 Ok(resp)
             }
-            Err(e) => {
+            Ok(Err(e)) => {
                 error!("Upstream error: {}", e);
                 let mut response = Response::new(Body::new(Bytes::from_static(b"502 Bad Gateway")));
                 *response.status_mut() = StatusCode::BAD_GATEWAY;
                 Ok(response)
             }
+            Err(_) => {
+                error!("Upstream timed out after {:?}: {} {}", self.upstream_config.timeout, method, url);
+
+                let event = CacheEvent {
+                    url: url.clone(),
+                    method: method.clone(),
+                    status: 504,
+                    cache_key: cache_key.to_string(),
+                    cache_status: "TIMEOUT",
+                    timestamp: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs(),
+                    headers: HashMap::new(),
+                    user_id,
+                    username,
+                    client_ip,
+                    domain: Self::extract_domain(&url),
+                    response_size: 0,
+                    request_duration_ms: request_start.elapsed().as_millis() as u64,
+                    content_type: None,
+                    user_agent: None,
+                };
+                self.send_to_kafka_async(event);
+
+                let mut response = Response::new(Body::new(Bytes::from_static(b"504 Gateway Timeout")));
+                *response.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+                Ok(response)
+            }
         }
     }
 }
 
+/// Build an upstream certificate verification override from
+/// `UPSTREAM_INSECURE_DOMAINS` (comma-separated host suffixes that accept
+/// any presented chain) — the simplest instance of the pluggable verifier
+/// hook, for origins sitting behind self-signed certs.
+fn build_upstream_verify_override_from_env() -> Option<UpstreamVerifyOverride> {
+    let insecure_domains: Vec<String> = std::env::var("UPSTREAM_INSECURE_DOMAINS")
+        .ok()?
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if insecure_domains.is_empty() {
+        return None;
+    }
+
+    warn!(
+        "Upstream cert verification relaxed for domains: {:?}",
+        insecure_domains
+    );
+
+    Some(Arc::new(move |_chain: &[CertificateDer<'static>], host: &str| {
+        let host = host.to_lowercase();
+        insecure_domains
+            .iter()
+            .any(|d| host == *d || host.ends_with(&format!(".{}", d)))
+    }))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt()
@@ -447,7 +1567,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         max_body_size,
     };
 
-    let service = Arc::new(ProxyService::new(cert_cache, cache_config.clone(), kafka_brokers));
+    let upstream_verify_override = build_upstream_verify_override_from_env();
+    let mitm_config = MitmConfig::from_env();
+    if mitm_config.enabled {
+        info!(
+            "🔓 MITM_MODE enabled, excluded domains: {:?}",
+            mitm_config.excluded_domains
+        );
+    }
+    let upstream_config = UpstreamConfig::from_env();
+
+    let disk_cache_config = DiskCacheConfig::from_env();
+    let disk_cache = if disk_cache_config.enabled {
+        match DiskCache::open(&disk_cache_config).await {
+            Ok(cache) => {
+                info!("💾 Disk cache enabled at {:?}, budget {} bytes", disk_cache_config.dir, disk_cache_config.max_bytes);
+                Some(Arc::new(cache))
+            }
+            Err(e) => {
+                error!("Disk cache disabled: failed to open {:?}: {}", disk_cache_config.dir, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let access_token_config = AccessTokenConfig::from_env();
+    if access_token_config.enabled() {
+        info!(
+            "🔑 PROXY_ACCESS_TOKEN_SECRET set, gating access (always-allowed domains: {:?})",
+            access_token_config.always_allowed_domains
+        );
+    }
+
+    let service = Arc::new(ProxyService::new(
+        cert_cache,
+        mitm_config,
+        cache_config.clone(),
+        disk_cache,
+        upstream_config,
+        access_token_config,
+        kafka_brokers,
+        upstream_verify_override,
+    ));
     let http_port = std::env::var("HTTP_PORT")
         .ok()
         .and_then(|s| s.parse().ok())
@@ -455,21 +1618,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let listener = TcpListener::bind(format!("0.0.0.0:{}", http_port)).await?;
     info!("🚀 BSDM-Proxy v2.0 (optimized) on 0.0.0.0:{}", http_port);
-    info!("📦 Cache: {} entries, TTL: {:?}, max body: {}MB", 
-        service.http_cache.capacity(), 
+    info!("📦 Cache: {} entries, TTL: {:?}, max body: {}MB",
+        service.http_cache.capacity(),
         cache_config.default_ttl,
         max_body_size / 1024 / 1024
     );
 
+    let shutdown = Shutdown::new();
+    let shutdown_grace = Duration::from_secs(
+        std::env::var("SHUTDOWN_GRACE_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30),
+    );
+
     loop {
-        let (stream, addr) = listener.accept().await?;
-        let service_clone = service.clone();
-        let client_ip = addr.ip().to_string();
-        
-        tokio::spawn(async move {
-            handle_connection(stream, addr, service_clone, client_ip).await;
-        });
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted?;
+                let service_clone = service.clone();
+                let client_ip = addr.ip().to_string();
+                let shutdown_clone = shutdown.clone();
+
+                shutdown.begin_connection();
+                tokio::spawn(async move {
+                    handle_connection(stream, addr, service_clone, client_ip).await;
+                    shutdown_clone.end_connection();
+                });
+            }
+            _ = Shutdown::wait_for_signal() => {
+                info!("Shutdown signal received, no longer accepting new connections");
+                shutdown.trigger();
+                break;
+            }
+        }
     }
+
+    info!("Draining up to {:?} for in-flight connections to finish", shutdown_grace);
+    shutdown.drain(shutdown_grace).await;
+
+    info!("Flushing Kafka producer before exit");
+    service.flush_kafka(Duration::from_secs(10));
+
+    Ok(())
 }
 
 async fn handle_connection(
@@ -478,6 +1669,7 @@ async fn handle_connection(
     service: Arc<ProxyService>,
     client_ip: String,
 ) {
+    set_tcp_keepalive(&stream, service.upstream_config.tcp_keepalive);
     let io = TokioIo::new(stream);
     let svc = service_fn(move |req: Request<Incoming>| {
         let service = service.clone();
@@ -491,6 +1683,30 @@ async fn handle_connection(
                     .as_str()
                     .to_string();
                 
+                let domain = authority.split(':').next().unwrap_or("unknown").to_string();
+                let terminate_tls = service.mitm_config.should_terminate(&domain);
+
+                // Verified once per CONNECT'd session rather than per inner
+                // request: a MITM'd browser never attaches
+                // Proxy-Authorization to requests on the tunnel it believes
+                // goes straight to the origin, so `serve_mitm_connection`
+                // carries this subject through instead of re-checking.
+                let mut authenticated_subject: Option<Arc<str>> = None;
+                if service.access_token_config.enabled()
+                    && !service.access_token_config.is_always_allowed(&domain)
+                {
+                    let subject = match ProxyService::extract_access_token(&req) {
+                        Some(token) => service.verify_access_token(&token).await,
+                        None => None,
+                    };
+                    if subject.is_none() {
+                        return Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+                            ProxyService::proxy_auth_required_response(),
+                        );
+                    }
+                    authenticated_subject = subject;
+                }
+
                 tokio::spawn({
                     let service = service.clone();
                     let client_ip = client_ip.clone();
@@ -498,44 +1714,28 @@ async fn handle_connection(
                         match hyper::upgrade::on(req).await {
                             Ok(upgraded) => {
                                 // Оборачиваем Upgraded в TokioIo для AsyncRead/AsyncWrite
-                                let mut client_io = TokioIo::new(upgraded);
-                                
-                                match TcpStream::connect(&authority).await {
-                                    Ok(mut upstream) => {
-                                        // Bidirectional copy между клиентом и upstream
-                                        match copy_bidirectional(&mut client_io, &mut upstream).await {
-                                            Ok((bytes_c2u, bytes_u2c)) => {
-                                                let duration_ms = request_start.elapsed().as_millis() as u64;
-                                                let domain = authority.split(':').next().unwrap_or("unknown").to_string();
-                                                
-                                                let event = CacheEvent {
-                                                    url: format!("https://{}", authority),
-                                                    method: "CONNECT".to_string(),
-                                                    status: 200,
-                                                    cache_key: service.generate_cache_key("CONNECT", &authority).to_string(),
-                                                    cache_status: "BYPASS",
-                                                    timestamp: SystemTime::now()
-                                                        .duration_since(SystemTime::UNIX_EPOCH)
-                                                        .unwrap_or_default()
-                                                        .as_secs(),
-                                                    headers: HashMap::new(),
-                                                    user_id: None,
-                                                    username: None,
-                                                    client_ip,
-                                                    domain,
-                                                    response_size: bytes_u2c,
-                                                    request_duration_ms: duration_ms,
-                                                    content_type: None,
-                                                    user_agent: None,
-                                                };
-                                                
-                                                service.send_to_kafka_async(event);
-                                                debug!("CONNECT closed: {}↑ {}↓", bytes_c2u, bytes_u2c);
-                                            }
-                                            Err(e) => error!("CONNECT copy failed: {}", e),
-                                        }
-                                    }
-                                    Err(e) => error!("CONNECT upstream failed: {}", e),
+                                let client_io = TokioIo::new(upgraded);
+
+                                if terminate_tls {
+                                    serve_mitm_connection(
+                                        client_io,
+                                        &domain,
+                                        service,
+                                        client_ip,
+                                        request_start,
+                                        authenticated_subject,
+                                    )
+                                    .await;
+                                } else {
+                                    tunnel_connect(
+                                        client_io,
+                                        &authority,
+                                        &domain,
+                                        &service,
+                                        client_ip,
+                                        request_start,
+                                    )
+                                    .await;
                                 }
                             }
                             Err(e) => error!("Upgrade failed: {}", e),
@@ -548,7 +1748,7 @@ async fn handle_connection(
                     .body(Body::new(Bytes::new()))?;
                 return Ok::<_, Box<dyn std::error::Error + Send + Sync>>(response);
             }
-            service.handle_request(req, client_ip).await
+            service.handle_request(req, client_ip, None).await
         }
     });
 
@@ -562,3 +1762,101 @@ async fn handle_connection(
         error!("Connection error from {}: {}", addr, e);
     }
 }
+
+/// Blind byte-for-byte tunnel between client and upstream (the historical
+/// CONNECT behavior, and the fallback for MITM-excluded domains).
+async fn tunnel_connect(
+    mut client_io: TokioIo<hyper::upgrade::Upgraded>,
+    authority: &str,
+    domain: &str,
+    service: &Arc<ProxyService>,
+    client_ip: String,
+    request_start: Instant,
+) {
+    match tokio::time::timeout(service.upstream_config.timeout, TcpStream::connect(authority)).await {
+        Ok(Ok(mut upstream)) => {
+            set_tcp_keepalive(&upstream, service.upstream_config.tcp_keepalive);
+            match copy_bidirectional(&mut client_io, &mut upstream).await {
+                Ok((bytes_c2u, bytes_u2c)) => {
+                    let duration_ms = request_start.elapsed().as_millis() as u64;
+
+                    let event = CacheEvent {
+                        url: format!("https://{}", authority),
+                        method: "CONNECT".to_string(),
+                        status: 200,
+                        cache_key: service.generate_cache_key("CONNECT", authority).to_string(),
+                        cache_status: "BYPASS",
+                        timestamp: SystemTime::now()
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs(),
+                        headers: HashMap::new(),
+                        user_id: None,
+                        username: None,
+                        client_ip,
+                        domain: domain.to_string(),
+                        response_size: bytes_u2c,
+                        request_duration_ms: duration_ms,
+                        content_type: None,
+                        user_agent: None,
+                    };
+
+                    service.send_to_kafka_async(event);
+                    debug!("CONNECT closed: {}↑ {}↓", bytes_c2u, bytes_u2c);
+                }
+                Err(e) => error!("CONNECT copy failed: {}", e),
+            }
+        }
+        Ok(Err(e)) => error!("CONNECT upstream failed: {}", e),
+        Err(_) => error!(
+            "CONNECT upstream timed out after {:?} connecting to {}",
+            service.upstream_config.timeout, authority
+        ),
+    }
+}
+
+/// Terminate TLS for a CONNECT'd domain using a cert minted by `CertCache`,
+/// then run every request on the decrypted connection back through the
+/// normal caching pipeline instead of tunneling opaque bytes.
+async fn serve_mitm_connection(
+    client_io: TokioIo<hyper::upgrade::Upgraded>,
+    domain: &str,
+    service: Arc<ProxyService>,
+    client_ip: String,
+    request_start: Instant,
+    authenticated_subject: Option<Arc<str>>,
+) {
+    let tls_config = match service.cert_cache.get_or_generate_tls_config(domain).await {
+        Ok(config) => config,
+        Err(e) => {
+            error!("MITM cert generation failed for {}: {}", domain, e);
+            return;
+        }
+    };
+
+    let tls_stream = match TlsAcceptor::from(tls_config).accept(client_io).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("MITM TLS handshake failed for {}: {}", domain, e);
+            return;
+        }
+    };
+
+    let io = TokioIo::new(tls_stream);
+    let domain = domain.to_string();
+    let svc = service_fn(move |req: Request<Incoming>| {
+        let service = service.clone();
+        let client_ip = client_ip.clone();
+        let authenticated_subject = authenticated_subject.clone();
+        async move { service.handle_request(req, client_ip, authenticated_subject).await }
+    });
+
+    if let Err(e) = http1::Builder::new().serve_connection(io, svc).await {
+        debug!(
+            "MITM connection to {} closed after {}ms: {}",
+            domain,
+            request_start.elapsed().as_millis(),
+            e
+        );
+    }
+}
@@ -1,4 +1,4 @@
-//! Prometheus metrics for BSDM-Proxy
+//! Metrics for BSDM-Proxy
 //!
 //! Comprehensive metrics collection for monitoring proxy performance:
 //! - Request counters (total, by method, by status)
@@ -6,194 +6,527 @@
 //! - Latency histograms (request duration, cache lookup, upstream)
 //! - Upstream connection pool metrics
 //! - Memory usage and cache size
-
-use prometheus::{
-    Counter, CounterVec, Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, Opts, Registry,
-    TextEncoder,
+//!
+//! Every instrument below is backed by [`MetricsBackend::Prometheus`] (pull,
+//! via [`Metrics::export`]), [`MetricsBackend::Otlp`] (push, via a collector
+//! over OTLP), or both at once - selected by [`MetricsConfig`]. Call sites
+//! (`RequestMetricsGuard`, the ACL engine, etc.) work unchanged regardless of
+//! which backend(s) are active; they only ever see the wrapper types below.
+
+use bytes::Bytes;
+use hyper::body::Incoming;
+use hyper::header::AUTHORIZATION;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use opentelemetry::metrics::{
+    Counter as OtelCounter, Gauge as OtelGauge, Histogram as OtelHistogram, Meter, MeterProvider as _,
 };
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::runtime;
+use prometheus::{Counter, CounterVec, Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, Opts, Registry, TextEncoder};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+type Body = http_body_util::Full<Bytes>;
+
+/// Telemetry backend(s) a [`Metrics`] instance pushes/exposes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsBackend {
+    /// Prometheus pull-based `/metrics` text exposition only.
+    Prometheus,
+    /// OTLP push export to a collector only.
+    Otlp,
+    /// Both simultaneously.
+    Both,
+}
+
+impl MetricsBackend {
+    fn wants_prometheus(self) -> bool {
+        matches!(self, MetricsBackend::Prometheus | MetricsBackend::Both)
+    }
+
+    fn wants_otlp(self) -> bool {
+        matches!(self, MetricsBackend::Otlp | MetricsBackend::Both)
+    }
+}
+
+/// Configuration selecting which telemetry backend(s) [`Metrics`] is wired to.
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub backend: MetricsBackend,
+    /// OTLP collector gRPC endpoint, e.g. `"http://localhost:4317"`. Only
+    /// consulted when `backend` wants OTLP.
+    pub otlp_endpoint: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            backend: MetricsBackend::Prometheus,
+            otlp_endpoint: "http://localhost:4317".to_string(),
+        }
+    }
+}
+
+/// Builds an instrument against whichever backend(s) are active, so every
+/// definition in `Metrics::with_config` stays a single call regardless of
+/// how many backends end up receiving the recorded value.
+struct Instruments {
+    registry: Option<Registry>,
+    meter: Option<Meter>,
+}
+
+impl Instruments {
+    fn counter(&self, name: &str, help: &str) -> Result<MetricCounter, Box<dyn std::error::Error>> {
+        let prometheus = match &self.registry {
+            Some(registry) => {
+                let c = Counter::new(name, help)?;
+                registry.register(Box::new(c.clone()))?;
+                Some(c)
+            }
+            None => None,
+        };
+        let otel = self.meter.as_ref().map(|m| m.u64_counter(name.to_string()).build());
+        Ok(MetricCounter { prometheus, otel })
+    }
+
+    fn counter_vec(
+        &self,
+        name: &str,
+        help: &str,
+        labels: &'static [&'static str],
+    ) -> Result<MetricCounterVec, Box<dyn std::error::Error>> {
+        let prometheus = match &self.registry {
+            Some(registry) => {
+                let c = CounterVec::new(Opts::new(name, help), labels)?;
+                registry.register(Box::new(c.clone()))?;
+                Some(c)
+            }
+            None => None,
+        };
+        let otel = self.meter.as_ref().map(|m| m.u64_counter(name.to_string()).build());
+        Ok(MetricCounterVec { label_names: labels, prometheus, otel })
+    }
+
+    fn gauge(&self, name: &str, help: &str) -> Result<MetricGauge, Box<dyn std::error::Error>> {
+        let prometheus = match &self.registry {
+            Some(registry) => {
+                let g = Gauge::new(name, help)?;
+                registry.register(Box::new(g.clone()))?;
+                Some(g)
+            }
+            None => None,
+        };
+        let otel = self.meter.as_ref().map(|m| m.f64_gauge(name.to_string()).build());
+        Ok(MetricGauge { prometheus, otel, fallback: Arc::new(AtomicU64::new(0)) })
+    }
+
+    fn histogram(
+        &self,
+        name: &str,
+        help: &str,
+        buckets: Vec<f64>,
+    ) -> Result<MetricHistogram, Box<dyn std::error::Error>> {
+        let prometheus = match &self.registry {
+            Some(registry) => {
+                let h = Histogram::with_opts(HistogramOpts::new(name, help).buckets(buckets))?;
+                registry.register(Box::new(h.clone()))?;
+                Some(h)
+            }
+            None => None,
+        };
+        let otel = self.meter.as_ref().map(|m| m.f64_histogram(name.to_string()).build());
+        Ok(MetricHistogram { prometheus, otel })
+    }
+
+    fn histogram_vec(
+        &self,
+        name: &str,
+        help: &str,
+        buckets: Vec<f64>,
+        labels: &'static [&'static str],
+    ) -> Result<MetricHistogramVec, Box<dyn std::error::Error>> {
+        let prometheus = match &self.registry {
+            Some(registry) => {
+                let h = HistogramVec::new(HistogramOpts::new(name, help).buckets(buckets), labels)?;
+                registry.register(Box::new(h.clone()))?;
+                Some(h)
+            }
+            None => None,
+        };
+        let otel = self.meter.as_ref().map(|m| m.f64_histogram(name.to_string()).build());
+        Ok(MetricHistogramVec { label_names: labels, prometheus, otel })
+    }
+}
+
+/// Counter backed by Prometheus, OTLP, or both.
+#[derive(Clone)]
+pub struct MetricCounter {
+    prometheus: Option<Counter>,
+    otel: Option<OtelCounter<u64>>,
+}
+
+impl MetricCounter {
+    pub fn inc(&self) {
+        if let Some(c) = &self.prometheus {
+            c.inc();
+        }
+        if let Some(c) = &self.otel {
+            c.add(1, &[]);
+        }
+    }
+
+    /// Current value, as tracked by the Prometheus side. OTLP is push-only
+    /// and has no locally readable value, so this returns 0.0 when
+    /// Prometheus isn't active.
+    pub fn get(&self) -> f64 {
+        self.prometheus.as_ref().map(|c| c.get()).unwrap_or(0.0)
+    }
+}
+
+/// Labeled counter backed by Prometheus, OTLP, or both. Mirrors
+/// `CounterVec::with_label_values` so existing call sites don't change.
+#[derive(Clone)]
+pub struct MetricCounterVec {
+    label_names: &'static [&'static str],
+    prometheus: Option<CounterVec>,
+    otel: Option<OtelCounter<u64>>,
+}
+
+impl MetricCounterVec {
+    pub fn with_label_values(&self, values: &[&str]) -> MetricCounterHandle {
+        MetricCounterHandle {
+            prometheus: self.prometheus.as_ref().map(|c| c.with_label_values(values)),
+            otel: self.otel.clone(),
+            attributes: label_attributes(self.label_names, values),
+        }
+    }
+}
+
+pub struct MetricCounterHandle {
+    prometheus: Option<Counter>,
+    otel: Option<OtelCounter<u64>>,
+    attributes: Vec<KeyValue>,
+}
+
+impl MetricCounterHandle {
+    pub fn inc(&self) {
+        if let Some(c) = &self.prometheus {
+            c.inc();
+        }
+        if let Some(c) = &self.otel {
+            c.add(1, &self.attributes);
+        }
+    }
+}
+
+/// Gauge backed by Prometheus, OTLP, or both. OTLP has no native
+/// increment/decrement instrument, so `inc`/`dec`/`add` track the current
+/// value themselves (reading it back from the Prometheus gauge when one is
+/// active, or from `fallback` when OTLP is the only backend) and record the
+/// resulting absolute value.
+#[derive(Clone)]
+pub struct MetricGauge {
+    prometheus: Option<Gauge>,
+    otel: Option<OtelGauge<f64>>,
+    fallback: Arc<AtomicU64>,
+}
+
+impl MetricGauge {
+    fn current(&self) -> f64 {
+        match &self.prometheus {
+            Some(g) => g.get(),
+            None => f64::from_bits(self.fallback.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn record(&self, value: f64) {
+        if let Some(otel) = &self.otel {
+            otel.record(value, &[]);
+        }
+        if self.prometheus.is_none() {
+            self.fallback.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn set(&self, value: f64) {
+        if let Some(g) = &self.prometheus {
+            g.set(value);
+        }
+        self.record(value);
+    }
+
+    pub fn add(&self, delta: f64) {
+        let new_value = self.current() + delta;
+        if let Some(g) = &self.prometheus {
+            g.add(delta);
+        }
+        self.record(new_value);
+    }
+
+    pub fn inc(&self) {
+        self.add(1.0);
+    }
+
+    pub fn dec(&self) {
+        self.add(-1.0);
+    }
+
+    pub fn get(&self) -> f64 {
+        self.current()
+    }
+}
+
+/// Histogram backed by Prometheus, OTLP, or both.
+#[derive(Clone)]
+pub struct MetricHistogram {
+    prometheus: Option<Histogram>,
+    otel: Option<OtelHistogram<f64>>,
+}
+
+impl MetricHistogram {
+    pub fn observe(&self, value: f64) {
+        if let Some(h) = &self.prometheus {
+            h.observe(value);
+        }
+        if let Some(h) = &self.otel {
+            h.record(value, &[]);
+        }
+    }
+}
+
+/// Labeled histogram backed by Prometheus, OTLP, or both. Mirrors
+/// `HistogramVec::with_label_values` so existing call sites don't change.
+#[derive(Clone)]
+pub struct MetricHistogramVec {
+    label_names: &'static [&'static str],
+    prometheus: Option<HistogramVec>,
+    otel: Option<OtelHistogram<f64>>,
+}
+
+impl MetricHistogramVec {
+    pub fn with_label_values(&self, values: &[&str]) -> MetricHistogramHandle {
+        MetricHistogramHandle {
+            prometheus: self.prometheus.as_ref().map(|h| h.with_label_values(values)),
+            otel: self.otel.clone(),
+            attributes: label_attributes(self.label_names, values),
+        }
+    }
+}
+
+pub struct MetricHistogramHandle {
+    prometheus: Option<Histogram>,
+    otel: Option<OtelHistogram<f64>>,
+    attributes: Vec<KeyValue>,
+}
+
+impl MetricHistogramHandle {
+    pub fn observe(&self, value: f64) {
+        if let Some(h) = &self.prometheus {
+            h.observe(value);
+        }
+        if let Some(h) = &self.otel {
+            h.record(value, &self.attributes);
+        }
+    }
+}
+
+fn label_attributes(names: &'static [&'static str], values: &[&str]) -> Vec<KeyValue> {
+    names.iter().zip(values).map(|(name, value)| KeyValue::new(*name, value.to_string())).collect()
+}
 
 /// Global metrics registry
 #[derive(Clone)]
 pub struct Metrics {
-    pub registry: Registry,
+    pub registry: Option<Registry>,
+    otel_provider: Option<SdkMeterProvider>,
 
     // Request metrics
-    pub requests_total: CounterVec,
-    pub requests_in_flight: Gauge,
-    pub request_duration_seconds: HistogramVec,
-    pub request_size_bytes: Histogram,
-    pub response_size_bytes: Histogram,
+    pub requests_total: MetricCounterVec,
+    pub requests_in_flight: MetricGauge,
+    pub request_duration_seconds: MetricHistogramVec,
+    pub request_size_bytes: MetricHistogram,
+    pub response_size_bytes: MetricHistogram,
 
     // Cache metrics
-    pub cache_hits_total: Counter,
-    pub cache_misses_total: Counter,
-    pub cache_bypasses_total: Counter,
-    pub cache_entries: Gauge,
-    pub cache_size_bytes: Gauge,
-    pub cache_evictions_total: Counter,
-    pub cache_lookup_duration_seconds: Histogram,
+    pub cache_hits_total: MetricCounter,
+    pub cache_misses_total: MetricCounter,
+    pub cache_bypasses_total: MetricCounter,
+    pub cache_entries: MetricGauge,
+    pub cache_size_bytes: MetricGauge,
+    pub cache_evictions_total: MetricCounter,
+    pub cache_lookup_duration_seconds: MetricHistogram,
 
     // Upstream metrics
-    pub upstream_requests_total: CounterVec,
-    pub upstream_duration_seconds: HistogramVec,
-    pub upstream_errors_total: CounterVec,
-    pub upstream_connections_active: Gauge,
-    pub upstream_connections_created: Counter,
+    pub upstream_requests_total: MetricCounterVec,
+    pub upstream_duration_seconds: MetricHistogramVec,
+    pub upstream_errors_total: MetricCounterVec,
+    pub upstream_connections_active: MetricGauge,
+    pub upstream_connections_created: MetricCounter,
+
+    // ACL metrics
+    pub acl_decisions_total: MetricCounterVec,
+    pub acl_evaluation_duration_seconds: MetricHistogram,
 
     // System metrics
-    pub kafka_events_sent: Counter,
-    pub kafka_send_errors: Counter,
-    pub tls_handshakes_total: CounterVec,
+    pub kafka_events_sent: MetricCounter,
+    pub kafka_send_errors: MetricCounter,
+    pub tls_handshakes_total: MetricCounterVec,
 }
 
 impl Metrics {
-    /// Create new metrics registry with all metrics registered
+    /// Create a metrics registry backed by Prometheus pull-based exposition
+    /// only (the historical default).
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let registry = Registry::new();
+        Self::with_config(MetricsConfig::default())
+    }
+
+    /// Create a metrics registry backed by whichever telemetry backend(s)
+    /// `config.backend` selects.
+    pub fn with_config(config: MetricsConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let registry = if config.backend.wants_prometheus() {
+            Some(Registry::new())
+        } else {
+            None
+        };
+
+        let otel_provider = if config.backend.wants_otlp() {
+            let exporter = opentelemetry_otlp::MetricExporter::builder()
+                .with_tonic()
+                .with_endpoint(&config.otlp_endpoint)
+                .build()?;
+            let reader = PeriodicReader::builder(exporter, runtime::Tokio).build();
+            Some(SdkMeterProvider::builder().with_reader(reader).build())
+        } else {
+            None
+        };
+        let meter = otel_provider.as_ref().map(|p| p.meter("bsdm_proxy"));
+
+        let instruments = Instruments { registry: registry.clone(), meter };
 
         // Request metrics
-        let requests_total = CounterVec::new(
-            Opts::new("bsdm_proxy_requests_total", "Total number of HTTP requests"),
+        let requests_total = instruments.counter_vec(
+            "bsdm_proxy_requests_total",
+            "Total number of HTTP requests",
             &["method", "status", "cache_status"],
         )?;
-        registry.register(Box::new(requests_total.clone()))?;
 
-        let requests_in_flight = Gauge::new(
+        let requests_in_flight = instruments.gauge(
             "bsdm_proxy_requests_in_flight",
             "Number of requests currently being processed",
         )?;
-        registry.register(Box::new(requests_in_flight.clone()))?;
-
-        let request_duration_seconds = HistogramVec::new(
-            HistogramOpts::new(
-                "bsdm_proxy_request_duration_seconds",
-                "HTTP request duration in seconds",
-            )
-            .buckets(vec![
-                0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
-            ]),
+
+        let request_duration_seconds = instruments.histogram_vec(
+            "bsdm_proxy_request_duration_seconds",
+            "HTTP request duration in seconds",
+            vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
             &["method", "cache_status"],
         )?;
-        registry.register(Box::new(request_duration_seconds.clone()))?;
 
-        let request_size_bytes = Histogram::with_opts(HistogramOpts::new(
+        let request_size_bytes = instruments.histogram(
             "bsdm_proxy_request_size_bytes",
             "HTTP request size in bytes",
-        )
-        .buckets(vec![
-            100.0, 1000.0, 10000.0, 100000.0, 1000000.0, 10000000.0,
-        ]))?;
-        registry.register(Box::new(request_size_bytes.clone()))?;
+            vec![100.0, 1000.0, 10000.0, 100000.0, 1000000.0, 10000000.0],
+        )?;
 
-        let response_size_bytes = Histogram::with_opts(HistogramOpts::new(
+        let response_size_bytes = instruments.histogram(
             "bsdm_proxy_response_size_bytes",
             "HTTP response size in bytes",
-        )
-        .buckets(vec![
-            100.0, 1000.0, 10000.0, 100000.0, 1000000.0, 10000000.0,
-        ]))?;
-        registry.register(Box::new(response_size_bytes.clone()))?;
+            vec![100.0, 1000.0, 10000.0, 100000.0, 1000000.0, 10000000.0],
+        )?;
 
         // Cache metrics
         let cache_hits_total =
-            Counter::new("bsdm_proxy_cache_hits_total", "Total number of cache hits")?;
-        registry.register(Box::new(cache_hits_total.clone()))?;
+            instruments.counter("bsdm_proxy_cache_hits_total", "Total number of cache hits")?;
 
-        let cache_misses_total = Counter::new(
-            "bsdm_proxy_cache_misses_total",
-            "Total number of cache misses",
-        )?;
-        registry.register(Box::new(cache_misses_total.clone()))?;
+        let cache_misses_total =
+            instruments.counter("bsdm_proxy_cache_misses_total", "Total number of cache misses")?;
 
-        let cache_bypasses_total = Counter::new(
-            "bsdm_proxy_cache_bypasses_total",
-            "Total number of cache bypasses",
-        )?;
-        registry.register(Box::new(cache_bypasses_total.clone()))?;
+        let cache_bypasses_total =
+            instruments.counter("bsdm_proxy_cache_bypasses_total", "Total number of cache bypasses")?;
 
-        let cache_entries = Gauge::new(
-            "bsdm_proxy_cache_entries",
-            "Current number of entries in cache",
-        )?;
-        registry.register(Box::new(cache_entries.clone()))?;
+        let cache_entries =
+            instruments.gauge("bsdm_proxy_cache_entries", "Current number of entries in cache")?;
 
         let cache_size_bytes =
-            Gauge::new("bsdm_proxy_cache_size_bytes", "Current cache size in bytes")?;
-        registry.register(Box::new(cache_size_bytes.clone()))?;
+            instruments.gauge("bsdm_proxy_cache_size_bytes", "Current cache size in bytes")?;
 
-        let cache_evictions_total = Counter::new(
-            "bsdm_proxy_cache_evictions_total",
-            "Total number of cache evictions",
-        )?;
-        registry.register(Box::new(cache_evictions_total.clone()))?;
+        let cache_evictions_total =
+            instruments.counter("bsdm_proxy_cache_evictions_total", "Total number of cache evictions")?;
 
-        let cache_lookup_duration_seconds = Histogram::with_opts(HistogramOpts::new(
+        let cache_lookup_duration_seconds = instruments.histogram(
             "bsdm_proxy_cache_lookup_duration_seconds",
             "Cache lookup duration in seconds",
-        )
-        .buckets(vec![0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01]))?;
-        registry.register(Box::new(cache_lookup_duration_seconds.clone()))?;
+            vec![0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01],
+        )?;
 
         // Upstream metrics
-        let upstream_requests_total = CounterVec::new(
-            Opts::new(
-                "bsdm_proxy_upstream_requests_total",
-                "Total upstream requests",
-            ),
+        let upstream_requests_total = instruments.counter_vec(
+            "bsdm_proxy_upstream_requests_total",
+            "Total upstream requests",
             &["host", "status"],
         )?;
-        registry.register(Box::new(upstream_requests_total.clone()))?;
-
-        let upstream_duration_seconds = HistogramVec::new(
-            HistogramOpts::new(
-                "bsdm_proxy_upstream_duration_seconds",
-                "Upstream request duration in seconds",
-            )
-            .buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+
+        let upstream_duration_seconds = instruments.histogram_vec(
+            "bsdm_proxy_upstream_duration_seconds",
+            "Upstream request duration in seconds",
+            vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
             &["host"],
         )?;
-        registry.register(Box::new(upstream_duration_seconds.clone()))?;
 
-        let upstream_errors_total = CounterVec::new(
-            Opts::new("bsdm_proxy_upstream_errors_total", "Total upstream errors"),
+        let upstream_errors_total = instruments.counter_vec(
+            "bsdm_proxy_upstream_errors_total",
+            "Total upstream errors",
             &["host", "error_type"],
         )?;
-        registry.register(Box::new(upstream_errors_total.clone()))?;
 
-        let upstream_connections_active = Gauge::new(
+        let upstream_connections_active = instruments.gauge(
             "bsdm_proxy_upstream_connections_active",
             "Number of active upstream connections",
         )?;
-        registry.register(Box::new(upstream_connections_active.clone()))?;
 
-        let upstream_connections_created = Counter::new(
+        let upstream_connections_created = instruments.counter(
             "bsdm_proxy_upstream_connections_created_total",
             "Total upstream connections created",
         )?;
-        registry.register(Box::new(upstream_connections_created.clone()))?;
 
-        // System metrics
-        let kafka_events_sent = Counter::new(
-            "bsdm_proxy_kafka_events_sent_total",
-            "Total Kafka events sent",
+        // ACL metrics
+        let acl_decisions_total = instruments.counter_vec(
+            "bsdm_proxy_acl_decisions_total",
+            "Total ACL decisions",
+            &["action", "rule_id"],
         )?;
-        registry.register(Box::new(kafka_events_sent.clone()))?;
 
-        let kafka_send_errors = Counter::new(
-            "bsdm_proxy_kafka_send_errors_total",
-            "Total Kafka send errors",
+        let acl_evaluation_duration_seconds = instruments.histogram(
+            "bsdm_proxy_acl_evaluation_duration_seconds",
+            "ACL rule evaluation duration in seconds",
+            vec![0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05],
         )?;
-        registry.register(Box::new(kafka_send_errors.clone()))?;
 
-        let tls_handshakes_total = CounterVec::new(
-            Opts::new("bsdm_proxy_tls_handshakes_total", "Total TLS handshakes"),
+        // System metrics
+        let kafka_events_sent =
+            instruments.counter("bsdm_proxy_kafka_events_sent_total", "Total Kafka events sent")?;
+
+        let kafka_send_errors =
+            instruments.counter("bsdm_proxy_kafka_send_errors_total", "Total Kafka send errors")?;
+
+        let tls_handshakes_total = instruments.counter_vec(
+            "bsdm_proxy_tls_handshakes_total",
+            "Total TLS handshakes",
             &["status"],
         )?;
-        registry.register(Box::new(tls_handshakes_total.clone()))?;
 
         Ok(Metrics {
             registry,
+            otel_provider,
             requests_total,
             requests_in_flight,
             request_duration_seconds,
@@ -211,16 +544,20 @@ impl Metrics {
             upstream_errors_total,
             upstream_connections_active,
             upstream_connections_created,
+            acl_decisions_total,
+            acl_evaluation_duration_seconds,
             kafka_events_sent,
             kafka_send_errors,
             tls_handshakes_total,
         })
     }
 
-    /// Export metrics in Prometheus text format
+    /// Export metrics in Prometheus text format. Errors if this instance
+    /// wasn't configured with `MetricsBackend::Prometheus`/`Both`.
     pub fn export(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let registry = self.registry.as_ref().ok_or("Prometheus backend is not active for this Metrics instance")?;
         let encoder = TextEncoder::new();
-        let metric_families = self.registry.gather();
+        let metric_families = registry.gather();
         let mut buffer = Vec::new();
         encoder.encode(&metric_families, &mut buffer)?;
         Ok(buffer)
@@ -237,6 +574,17 @@ impl Metrics {
             hits / total
         }
     }
+
+    /// Flush and shut down the OTLP export pipeline, if one is active.
+    /// Should be called once during process shutdown so buffered metrics
+    /// aren't lost.
+    pub fn shutdown(&self) {
+        if let Some(provider) = &self.otel_provider {
+            if let Err(e) = provider.shutdown() {
+                warn!("Failed to shut down OTLP metrics pipeline: {}", e);
+            }
+        }
+    }
 }
 
 impl Default for Metrics {
@@ -288,3 +636,122 @@ impl RequestMetricsGuard {
             .observe(response_size as f64);
     }
 }
+
+/// Configuration for [`MetricsServer`]
+#[derive(Debug, Clone)]
+pub struct MetricsServerConfig {
+    pub listen_addr: SocketAddr,
+    /// Path the Prometheus exposition is served on; everything else gets 404.
+    pub path: String,
+    /// If set, the scrape endpoint requires `Authorization: Bearer <token>`
+    /// matching this value, rejecting mismatches with 401.
+    pub auth_token: Option<String>,
+}
+
+impl Default for MetricsServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: ([0, 0, 0, 0], 9100).into(),
+            path: "/metrics".to_string(),
+            auth_token: None,
+        }
+    }
+}
+
+/// Lightweight HTTP server exposing a [`Metrics`] registry's Prometheus text
+/// exposition, so embedders don't each have to wire up their own listener
+/// around [`Metrics::export`].
+pub struct MetricsServer {
+    metrics: Arc<Metrics>,
+    config: MetricsServerConfig,
+}
+
+impl MetricsServer {
+    pub fn new(metrics: Arc<Metrics>, config: MetricsServerConfig) -> Self {
+        Self { metrics, config }
+    }
+
+    /// Bind `config.listen_addr` and serve the scrape endpoint until
+    /// `shutdown` is notified. Connections already in flight when shutdown
+    /// fires are allowed to finish; no new connections are accepted after.
+    pub async fn serve(&self, shutdown: Arc<Notify>) -> std::io::Result<()> {
+        let listener = TcpListener::bind(self.config.listen_addr).await?;
+        info!("Metrics endpoint listening on {}{}", self.config.listen_addr, self.config.path);
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, addr) = accepted?;
+                    let metrics = self.metrics.clone();
+                    let path = self.config.path.clone();
+                    let auth_token = self.config.auth_token.clone();
+
+                    tokio::spawn(async move {
+                        let io = TokioIo::new(stream);
+                        let svc = service_fn(move |req: Request<Incoming>| {
+                            let metrics = metrics.clone();
+                            let path = path.clone();
+                            let auth_token = auth_token.clone();
+                            async move { Ok::<_, std::convert::Infallible>(handle_scrape(req, &metrics, &path, auth_token.as_deref())) }
+                        });
+
+                        if let Err(e) = http1::Builder::new().serve_connection(io, svc).await {
+                            error!("Metrics connection error from {}: {}", addr, e);
+                        }
+                    });
+                }
+                _ = shutdown.notified() => {
+                    info!("Metrics endpoint shutting down");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Handle a single scrape request: check the path, check bearer auth if
+/// configured, and serve the Prometheus exposition.
+fn handle_scrape(
+    req: Request<Incoming>,
+    metrics: &Metrics,
+    path: &str,
+    expected_token: Option<&str>,
+) -> Response<Body> {
+    if req.uri().path() != path {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::new(Bytes::new()))
+            .unwrap();
+    }
+
+    if let Some(expected) = expected_token {
+        let authorized = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|token| token == expected);
+
+        if !authorized {
+            return Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(Body::new(Bytes::new()))
+                .unwrap();
+        }
+    }
+
+    match metrics.export() {
+        Ok(buffer) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::new(Bytes::from(buffer)))
+            .unwrap(),
+        Err(e) => {
+            warn!("Failed to encode metrics: {}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::new(Bytes::new()))
+                .unwrap()
+        }
+    }
+}
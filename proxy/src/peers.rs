@@ -3,13 +3,24 @@
 //! Manages parent and sibling cache peers for hierarchical caching.
 //! Tracks peer health, RTT, statistics, and connection pools.
 
+use crate::selection::{SelectionPolicy, SelectionStrategy};
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
-use tracing::{debug, info, warn};
+use tokio::sync::{Notify, OwnedSemaphorePermit, RwLock, Semaphore};
+use tracing::{debug, error, info, warn};
+
+/// The most recent ICP response observed for a peer, surfaced for
+/// introspection (see [`PeerSnapshot`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IcpOutcome {
+    Hit,
+    Miss,
+}
 
 /// Type of cache peer
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -29,6 +40,18 @@ impl std::fmt::Display for PeerType {
     }
 }
 
+impl std::str::FromStr for PeerType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parent" => Ok(PeerType::Parent),
+            "sibling" => Ok(PeerType::Sibling),
+            other => Err(format!("Invalid peer type: {}", other)),
+        }
+    }
+}
+
 /// Statistics for a cache peer
 #[derive(Debug, Default)]
 pub struct PeerStats {
@@ -39,6 +62,11 @@ pub struct PeerStats {
     pub bytes_received: AtomicU64,
     pub last_success: RwLock<Option<Instant>>,
     pub last_failure: RwLock<Option<Instant>>,
+    /// Count of times `try_acquire_connection` found this peer's connection
+    /// pool already at `max_connections` and returned `None`. A peer with a
+    /// high count here relative to `requests` is chronically undersized for
+    /// its traffic and should have `max_connections` raised.
+    pub pool_exhausted: AtomicU64,
 }
 
 impl PeerStats {
@@ -126,13 +154,93 @@ impl PeerConfig {
     }
 }
 
+/// Starting value for a peer's `hit_ewma`: neutral until ICP gossip proves
+/// otherwise, so a freshly-added sibling isn't starved out by
+/// `GossipWeightedStrategy` before it's ever been queried.
+const INITIAL_HIT_EWMA: f64 = 0.5;
+
+/// A `CachePeer`'s circuit breaker state. `Closed` is normal operation;
+/// `Open` demotes the peer to probe-only (no live traffic routed, per
+/// `CachePeer::is_healthy`); `HalfOpen` is the trial period after the
+/// cool-down, where live traffic is routed to the peer again while
+/// `success_threshold` consecutive probes must succeed to close the circuit
+/// (a failed probe reopens it).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitState {
+    Closed = 0,
+    Open = 1,
+    HalfOpen = 2,
+}
+
+/// Tunables for a `CachePeer`'s circuit breaker. Kept separate from
+/// `PeerConfig` since it's operational policy rather than peer identity,
+/// and `PeerConfig` round-trips through the persistence store and API
+/// as-is. Override per peer via `CachePeer::with_circuit_config`.
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerConfig {
+    /// How long the circuit stays `Open` before the active prober is
+    /// allowed to move it to `HalfOpen` for a trial.
+    pub open_duration: Duration,
+    /// How often the active prober probes this peer, in any circuit state.
+    pub probe_interval: Duration,
+    /// Consecutive successful `HalfOpen` probes needed to close the
+    /// circuit again.
+    pub success_threshold: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            open_duration: Duration::from_secs(30),
+            probe_interval: Duration::from_secs(10),
+            success_threshold: 3,
+        }
+    }
+}
+
+/// A reserved slot in a `CachePeer`'s connection pool, obtained via
+/// `CachePeer::try_acquire_connection`/`acquire_connection`. Releases the
+/// slot back to the pool when dropped, so a forwarding caller should hold
+/// this for the lifetime of the upstream connection it represents.
+#[derive(Debug)]
+pub struct PooledConnection {
+    _permit: OwnedSemaphorePermit,
+}
+
 /// A cache peer (parent or sibling)
 #[derive(Debug)]
 pub struct CachePeer {
     pub id: String,
     pub config: PeerConfig,
-    pub healthy: AtomicBool,
+    circuit_state: AtomicU8,
+    /// Milliseconds after `created_at` at which the circuit last tripped to
+    /// `Open`, used to time the `circuit_config.open_duration` cool-down.
+    circuit_opened_at_ms: AtomicU64,
+    /// Milliseconds after `created_at` at which the active prober last
+    /// probed this peer, used to time `circuit_config.probe_interval`.
+    last_probed_at_ms: AtomicU64,
+    /// Consecutive successful probes observed while `HalfOpen`.
+    half_open_successes: AtomicU32,
+    circuit_config: CircuitBreakerConfig,
     pub rtt_ms: AtomicU64,
+    /// Sliding EWMA (alpha = 0.3) of this peer's ICP HIT ratio, stored as
+    /// `f64` bits since there's no `AtomicF64`. Fed by `record_icp_hit`/
+    /// `record_icp_miss`, read by `GossipWeightedStrategy`.
+    pub hit_ewma_bits: AtomicU64,
+    /// Last ICP response observed for this peer (0 = none yet, 1 = hit, 2 =
+    /// miss/timeout), surfaced via [`Self::last_icp_outcome`].
+    last_icp_outcome: AtomicU8,
+    /// Bounds concurrent outstanding ICP queries to this peer to
+    /// `config.max_connections`, so a slow or unresponsive sibling can't
+    /// accumulate an unbounded number of in-flight probes. See
+    /// `try_acquire_icp_slot`.
+    icp_semaphore: Arc<Semaphore>,
+    /// Bounds concurrent outstanding upstream connections to this peer to
+    /// `config.max_connections`, so a forwarding caller applies backpressure
+    /// (wait or fail over to the next-best peer) instead of opening an
+    /// unbounded number of sockets to an already-saturated parent/sibling.
+    /// See `try_acquire_connection`/`acquire_connection`.
+    connection_pool: Arc<Semaphore>,
     pub stats: PeerStats,
     pub created_at: Instant,
 }
@@ -140,30 +248,159 @@ pub struct CachePeer {
 impl CachePeer {
     pub fn new(config: PeerConfig) -> Self {
         let id = format!("{}:{}:{}", config.peer_type, config.host, config.port);
-        info!("Creating cache peer: {} (type: {}, weight: {})", 
+        info!("Creating cache peer: {} (type: {}, weight: {})",
               id, config.peer_type, config.weight);
-        
+
+        let icp_semaphore = Arc::new(Semaphore::new(config.max_connections.max(1)));
+        let connection_pool = Arc::new(Semaphore::new(config.max_connections.max(1)));
+
         Self {
             id,
             config,
-            healthy: AtomicBool::new(true),
+            circuit_state: AtomicU8::new(CircuitState::Closed as u8),
+            circuit_opened_at_ms: AtomicU64::new(0),
+            // Sentinel for "never probed", so a freshly-created peer isn't
+            // mistaken for one probed moments ago (elapsed-since-creation
+            // is itself near zero) and wrongly held back by
+            // `circuit_config.probe_interval` on its very first check.
+            last_probed_at_ms: AtomicU64::new(u64::MAX),
+            half_open_successes: AtomicU32::new(0),
+            circuit_config: CircuitBreakerConfig::default(),
             rtt_ms: AtomicU64::new(0),
+            hit_ewma_bits: AtomicU64::new(INITIAL_HIT_EWMA.to_bits()),
+            last_icp_outcome: AtomicU8::new(0),
+            icp_semaphore,
+            connection_pool,
             stats: PeerStats::new(),
             created_at: Instant::now(),
         }
     }
 
+    /// Override this peer's circuit breaker tunables instead of
+    /// `CircuitBreakerConfig::default()`.
+    pub fn with_circuit_config(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_config = config;
+        self
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.created_at.elapsed().as_millis() as u64
+    }
+
+    /// True only in `Closed`/`HalfOpen` - an `Open` peer gets no live
+    /// traffic, only probes from the active prober.
     pub fn is_healthy(&self) -> bool {
-        self.healthy.load(Ordering::Relaxed)
+        self.circuit_state() != CircuitState::Open
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        match self.circuit_state.load(Ordering::Relaxed) {
+            1 => CircuitState::Open,
+            2 => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
     }
 
+    /// Coarse health override used by passive, error-rate-based demotion
+    /// (`PeerRegistry::health_check`, `HierarchyManager::record_peer_error`):
+    /// `true` closes the circuit, `false` trips it open.
     pub fn set_healthy(&self, healthy: bool) {
-        let was_healthy = self.healthy.swap(healthy, Ordering::Relaxed);
-        if was_healthy != healthy {
-            if healthy {
-                info!("Peer {} is now healthy", self.id);
-            } else {
-                warn!("Peer {} is now unhealthy", self.id);
+        if healthy {
+            self.close_circuit();
+        } else {
+            self.trip_circuit();
+        }
+    }
+
+    /// Trip the circuit to `Open`, (re)starting its cool-down. Idempotent
+    /// in effect but always restarts the cool-down timer, since a failure
+    /// observed during a `HalfOpen` trial should push the cool-down back
+    /// out rather than leave it measured from the original trip.
+    pub fn trip_circuit(&self) {
+        let previous = self.circuit_state.swap(CircuitState::Open as u8, Ordering::Relaxed);
+        self.circuit_opened_at_ms.store(self.elapsed_ms(), Ordering::Relaxed);
+        self.half_open_successes.store(0, Ordering::Relaxed);
+        if previous != CircuitState::Open as u8 {
+            warn!("Peer {} circuit opened", self.id);
+        }
+    }
+
+    /// Close the circuit, restoring normal routing.
+    pub fn close_circuit(&self) {
+        let previous = self.circuit_state.swap(CircuitState::Closed as u8, Ordering::Relaxed);
+        self.half_open_successes.store(0, Ordering::Relaxed);
+        if previous != CircuitState::Closed as u8 {
+            info!("Peer {} circuit closed", self.id);
+        }
+    }
+
+    fn enter_half_open(&self) {
+        let previous = self.circuit_state.swap(CircuitState::HalfOpen as u8, Ordering::Relaxed);
+        if previous != CircuitState::HalfOpen as u8 {
+            self.half_open_successes.store(0, Ordering::Relaxed);
+            info!(
+                "Peer {} circuit half-open after {:?} cool-down, allowing trial probes",
+                self.id, self.circuit_config.open_duration
+            );
+        }
+    }
+
+    /// Whether the active health prober should probe this peer right now:
+    /// gated by `circuit_config.probe_interval` since the last probe in any
+    /// state, and additionally gated by `circuit_config.open_duration`'s
+    /// cool-down while `Open` - moving to `HalfOpen` as a side effect once
+    /// that cool-down elapses, so the next `record_probe_result` lands in
+    /// `HalfOpen` rather than just being swallowed by the `Open` fast path.
+    /// Marks this peer as probed now if it returns `true`.
+    pub fn ready_for_probe(&self) -> bool {
+        let last_probed = self.last_probed_at_ms.load(Ordering::Relaxed);
+        if last_probed != u64::MAX
+            && Duration::from_millis(self.elapsed_ms().saturating_sub(last_probed)) < self.circuit_config.probe_interval
+        {
+            return false;
+        }
+
+        if self.circuit_state() == CircuitState::Open {
+            let since_opened = Duration::from_millis(
+                self.elapsed_ms().saturating_sub(self.circuit_opened_at_ms.load(Ordering::Relaxed)),
+            );
+            if since_opened < self.circuit_config.open_duration {
+                return false;
+            }
+            self.enter_half_open();
+        }
+
+        self.last_probed_at_ms.store(self.elapsed_ms(), Ordering::Relaxed);
+        true
+    }
+
+    /// Feed an active probe's outcome into the circuit breaker. A failure
+    /// trips the circuit from `Closed` or `HalfOpen`; any result observed
+    /// while still `Open` is ignored. A success only matters while
+    /// `HalfOpen`: `circuit_config.success_threshold` consecutive successes
+    /// close the circuit; fewer leave it `HalfOpen` for another trial.
+    pub fn record_probe_result(&self, success: bool) {
+        match self.circuit_state() {
+            CircuitState::Closed => {
+                if !success {
+                    self.trip_circuit();
+                }
+            }
+            CircuitState::Open => {
+                // `ready_for_probe` already promotes to `HalfOpen` once the
+                // cool-down elapses, so a probe result observed while still
+                // `Open` is one last confirmation a too-early caller sent
+                // anyway - nothing to do either way.
+            }
+            CircuitState::HalfOpen => {
+                if success {
+                    let successes = self.half_open_successes.fetch_add(1, Ordering::Relaxed) + 1;
+                    if successes >= self.circuit_config.success_threshold {
+                        self.close_circuit();
+                    }
+                } else {
+                    self.trip_circuit();
+                }
             }
         }
     }
@@ -172,10 +409,91 @@ impl CachePeer {
         Duration::from_millis(self.rtt_ms.load(Ordering::Relaxed))
     }
 
+    /// Blend a new RTT sample into the running EWMA (alpha = 0.3), rather
+    /// than overwriting it, so a single slow/fast probe doesn't swing
+    /// `score()` around.
     pub fn update_rtt(&self, rtt: Duration) {
-        let rtt_ms = rtt.as_millis() as u64;
-        self.rtt_ms.store(rtt_ms, Ordering::Relaxed);
-        debug!("Peer {} RTT updated to {}ms", self.id, rtt_ms);
+        let sample_ms = rtt.as_millis() as u64;
+        let previous = self.rtt_ms.load(Ordering::Relaxed);
+        let smoothed = if previous == 0 {
+            sample_ms
+        } else {
+            (previous as f64 * 0.7 + sample_ms as f64 * 0.3).round() as u64
+        };
+        self.rtt_ms.store(smoothed, Ordering::Relaxed);
+        debug!("Peer {} RTT EWMA updated to {}ms", self.id, smoothed);
+    }
+
+    /// Current ICP hit-ratio EWMA (0.0-1.0), used by `GossipWeightedStrategy`
+    /// to bias selection toward peers recently observed to hold content.
+    pub fn hit_ewma(&self) -> f64 {
+        f64::from_bits(self.hit_ewma_bits.load(Ordering::Relaxed))
+    }
+
+    fn update_hit_ewma(&self, sample: f64) {
+        let previous = self.hit_ewma();
+        let smoothed = previous * 0.7 + sample * 0.3;
+        self.hit_ewma_bits.store(smoothed.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Blend an observed ICP HIT into the hit-ratio EWMA.
+    pub fn record_icp_hit(&self) {
+        self.update_hit_ewma(1.0);
+        self.last_icp_outcome.store(1, Ordering::Relaxed);
+        debug!("Peer {} hit EWMA updated to {:.2}", self.id, self.hit_ewma());
+    }
+
+    /// Blend an observed ICP MISS/timeout into the hit-ratio EWMA.
+    pub fn record_icp_miss(&self) {
+        self.update_hit_ewma(0.0);
+        self.last_icp_outcome.store(2, Ordering::Relaxed);
+        debug!("Peer {} hit EWMA updated to {:.2}", self.id, self.hit_ewma());
+    }
+
+    /// The most recent ICP response observed for this peer, if any.
+    pub fn last_icp_outcome(&self) -> Option<IcpOutcome> {
+        match self.last_icp_outcome.load(Ordering::Relaxed) {
+            1 => Some(IcpOutcome::Hit),
+            2 => Some(IcpOutcome::Miss),
+            _ => None,
+        }
+    }
+
+    /// Reserve a slot for an outstanding ICP query to this peer, bounded by
+    /// `config.max_connections`. Returns `None` if the peer already has that
+    /// many queries in flight, so the caller should skip probing it this
+    /// round rather than piling on behind a possibly-stalled sibling.
+    pub fn try_acquire_icp_slot(&self) -> Option<OwnedSemaphorePermit> {
+        self.icp_semaphore.clone().try_acquire_owned().ok()
+    }
+
+    /// Reserve a slot in this peer's connection pool, returning `None`
+    /// immediately (and recording the saturation in
+    /// `PeerStats::pool_exhausted`) if all `config.max_connections` slots
+    /// are already in use, rather than blocking. For a forwarding caller
+    /// that would rather fail over to the next-best peer than queue.
+    pub fn try_acquire_connection(&self) -> Option<PooledConnection> {
+        match self.connection_pool.clone().try_acquire_owned() {
+            Ok(permit) => Some(PooledConnection { _permit: permit }),
+            Err(_) => {
+                self.stats.pool_exhausted.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Reserve a slot in this peer's connection pool, waiting if all
+    /// `config.max_connections` slots are currently in use. For a
+    /// forwarding caller that would rather queue behind this peer than
+    /// fail over to another.
+    pub async fn acquire_connection(&self) -> PooledConnection {
+        let permit = self
+            .connection_pool
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("connection pool semaphore is never closed");
+        PooledConnection { _permit: permit }
     }
 
     pub fn score(&self) -> f64 {
@@ -193,65 +511,489 @@ impl CachePeer {
     pub fn address(&self) -> String {
         format!("{}:{}", self.config.host, self.config.port)
     }
+
+    /// Snapshot the restorable part of this peer's state (RTT EWMA,
+    /// counters, health) for persistence.
+    pub fn persisted_state(&self) -> PersistedPeerState {
+        PersistedPeerState {
+            rtt_ms: self.rtt_ms.load(Ordering::Relaxed),
+            hit_ewma: self.hit_ewma(),
+            requests: self.stats.requests.load(Ordering::Relaxed),
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            errors: self.stats.errors.load(Ordering::Relaxed),
+            bytes_received: self.stats.bytes_received.load(Ordering::Relaxed),
+            healthy: self.is_healthy(),
+        }
+    }
+
+    /// Structured, serializable view of this peer's current state, for
+    /// introspection/API use rather than `PeerRegistry::stats_summary`'s
+    /// flat human-readable string.
+    pub fn snapshot(&self) -> PeerSnapshot {
+        PeerSnapshot {
+            id: self.id.clone(),
+            peer_type: self.config.peer_type,
+            address: self.address(),
+            weight: self.config.weight,
+            rtt_ms: self.rtt_ms.load(Ordering::Relaxed),
+            hit_ewma: self.hit_ewma(),
+            requests: self.stats.requests.load(Ordering::Relaxed),
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            errors: self.stats.errors.load(Ordering::Relaxed),
+            hit_rate: self.stats.hit_rate(),
+            healthy: self.is_healthy(),
+            circuit_state: self.circuit_state(),
+            last_icp_outcome: self.last_icp_outcome(),
+            pool_exhausted: self.stats.pool_exhausted.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Restore counters/RTT/hit-EWMA/health from a previously persisted
+    /// snapshot.
+    pub fn restore_state(&self, state: &PersistedPeerState) {
+        self.rtt_ms.store(state.rtt_ms, Ordering::Relaxed);
+        self.hit_ewma_bits.store(state.hit_ewma.to_bits(), Ordering::Relaxed);
+        self.stats.requests.store(state.requests, Ordering::Relaxed);
+        self.stats.hits.store(state.hits, Ordering::Relaxed);
+        self.stats.misses.store(state.misses, Ordering::Relaxed);
+        self.stats.errors.store(state.errors, Ordering::Relaxed);
+        self.stats.bytes_received.store(state.bytes_received, Ordering::Relaxed);
+        self.set_healthy(state.healthy);
+        debug!("Peer {} restored from persisted state", self.id);
+    }
+}
+
+/// Structured, serializable snapshot of a peer's current state: identity,
+/// config, live RTT/hit-ratio EWMAs, counters, and health - everything an
+/// operator needs to see why the hierarchy picked (or skipped) this peer,
+/// without grepping debug logs.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerSnapshot {
+    pub id: String,
+    pub peer_type: PeerType,
+    pub address: String,
+    pub weight: f64,
+    pub rtt_ms: u64,
+    pub hit_ewma: f64,
+    pub requests: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub errors: u64,
+    pub hit_rate: f64,
+    pub healthy: bool,
+    pub circuit_state: CircuitState,
+    pub last_icp_outcome: Option<IcpOutcome>,
+    pub pool_exhausted: u64,
+}
+
+/// A peer's restorable state: RTT EWMA, ICP hit-ratio EWMA, request/hit/
+/// miss/error/byte counters, and the health flag. Reloaded at startup so
+/// `select_parent`/`query_siblings` don't have to re-learn peer quality
+/// from scratch.
+#[derive(Debug, Clone)]
+pub struct PersistedPeerState {
+    pub rtt_ms: u64,
+    pub hit_ewma: f64,
+    pub requests: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub errors: u64,
+    pub bytes_received: u64,
+    pub healthy: bool,
+}
+
+impl Default for PersistedPeerState {
+    fn default() -> Self {
+        Self {
+            rtt_ms: 0,
+            hit_ewma: INITIAL_HIT_EWMA,
+            requests: 0,
+            hits: 0,
+            misses: 0,
+            errors: 0,
+            bytes_received: 0,
+            healthy: false,
+        }
+    }
+}
+
+/// A pluggable store for peer health/performance history, so it survives a
+/// proxy restart. Implementations must keep writes off the hot path:
+/// `PeerRegistry` only ever calls `put` from its background flusher, never
+/// inline with `resolve_source`.
+#[async_trait]
+pub trait PeerStore: Send + Sync {
+    async fn get(&self, peer_id: &str) -> Option<PersistedPeerState>;
+    async fn put(&self, peer_id: &str, config: &PeerConfig, state: PersistedPeerState);
+    /// Every peer persisted by a previous run, config included, so a fresh
+    /// registry can reconstruct its whole peer list on startup rather than
+    /// only restoring stats for peers it's told about some other way.
+    /// Empty for a store that has never been written to.
+    async fn load_all(&self) -> Vec<(PeerConfig, PersistedPeerState)>;
+}
+
+/// SQLite-backed `PeerStore`. SQLite access is synchronous, so every
+/// operation runs on the blocking pool, matching the connection-handling
+/// pattern used for the on-disk response cache index.
+#[cfg(feature = "peer-store-sqlite")]
+use rusqlite::OptionalExtension;
+
+#[cfg(feature = "peer-store-sqlite")]
+pub struct SqlitePeerStore {
+    conn: Arc<std::sync::Mutex<rusqlite::Connection>>,
+}
+
+#[cfg(feature = "peer-store-sqlite")]
+impl SqlitePeerStore {
+    pub async fn open(db_path: &str) -> rusqlite::Result<Self> {
+        let db_path = db_path.to_string();
+        let conn = tokio::task::spawn_blocking(move || -> rusqlite::Result<rusqlite::Connection> {
+            let conn = rusqlite::Connection::open(db_path)?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS peer_state (
+                    peer_id TEXT PRIMARY KEY,
+                    host TEXT NOT NULL,
+                    port INTEGER NOT NULL,
+                    peer_type TEXT NOT NULL,
+                    weight REAL NOT NULL,
+                    icp_port INTEGER,
+                    max_connections INTEGER NOT NULL,
+                    rtt_ms INTEGER NOT NULL,
+                    hit_ewma REAL NOT NULL,
+                    requests INTEGER NOT NULL,
+                    hits INTEGER NOT NULL,
+                    misses INTEGER NOT NULL,
+                    errors INTEGER NOT NULL,
+                    bytes_received INTEGER NOT NULL,
+                    healthy INTEGER NOT NULL
+                )",
+            )?;
+            Ok(conn)
+        })
+        .await
+        .expect("peer store init task panicked")?;
+
+        Ok(Self { conn: Arc::new(std::sync::Mutex::new(conn)) })
+    }
+}
+
+#[cfg(feature = "peer-store-sqlite")]
+#[async_trait]
+impl PeerStore for SqlitePeerStore {
+    async fn get(&self, peer_id: &str) -> Option<PersistedPeerState> {
+        let conn = self.conn.clone();
+        let peer_id = peer_id.to_string();
+
+        let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<PersistedPeerState>> {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT rtt_ms, hit_ewma, requests, hits, misses, errors, bytes_received, healthy
+                 FROM peer_state WHERE peer_id = ?1",
+                rusqlite::params![peer_id],
+                |row| {
+                    Ok(PersistedPeerState {
+                        rtt_ms: row.get::<_, i64>(0)? as u64,
+                        hit_ewma: row.get::<_, f64>(1)?,
+                        requests: row.get::<_, i64>(2)? as u64,
+                        hits: row.get::<_, i64>(3)? as u64,
+                        misses: row.get::<_, i64>(4)? as u64,
+                        errors: row.get::<_, i64>(5)? as u64,
+                        bytes_received: row.get::<_, i64>(6)? as u64,
+                        healthy: row.get::<_, i64>(7)? != 0,
+                    })
+                },
+            )
+            .optional()
+        })
+        .await;
+
+        match result {
+            Ok(Ok(state)) => state,
+            Ok(Err(e)) => {
+                error!("Failed to load persisted state for peer {}: {}", peer_id, e);
+                None
+            }
+            Err(e) => {
+                error!("Peer store load task panicked for {}: {}", peer_id, e);
+                None
+            }
+        }
+    }
+
+    async fn put(&self, peer_id: &str, config: &PeerConfig, state: PersistedPeerState) {
+        let conn = self.conn.clone();
+        let peer_id = peer_id.to_string();
+        let config = config.clone();
+
+        let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<()> {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO peer_state (
+                    peer_id, host, port, peer_type, weight, icp_port, max_connections,
+                    rtt_ms, hit_ewma, requests, hits, misses, errors, bytes_received, healthy
+                 )
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+                 ON CONFLICT(peer_id) DO UPDATE SET
+                    host = excluded.host,
+                    port = excluded.port,
+                    peer_type = excluded.peer_type,
+                    weight = excluded.weight,
+                    icp_port = excluded.icp_port,
+                    max_connections = excluded.max_connections,
+                    rtt_ms = excluded.rtt_ms,
+                    hit_ewma = excluded.hit_ewma,
+                    requests = excluded.requests,
+                    hits = excluded.hits,
+                    misses = excluded.misses,
+                    errors = excluded.errors,
+                    bytes_received = excluded.bytes_received,
+                    healthy = excluded.healthy",
+                rusqlite::params![
+                    peer_id,
+                    config.host,
+                    config.port,
+                    config.peer_type.to_string(),
+                    config.weight,
+                    config.icp_port,
+                    config.max_connections as i64,
+                    state.rtt_ms as i64,
+                    state.hit_ewma,
+                    state.requests as i64,
+                    state.hits as i64,
+                    state.misses as i64,
+                    state.errors as i64,
+                    state.bytes_received as i64,
+                    state.healthy as i64,
+                ],
+            )?;
+            Ok(())
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Failed to persist state for peer {}: {}", peer_id, e),
+            Err(e) => error!("Peer store save task panicked for {}: {}", peer_id, e),
+        }
+    }
+
+    async fn load_all(&self) -> Vec<(PeerConfig, PersistedPeerState)> {
+        let conn = self.conn.clone();
+
+        let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<(PeerConfig, PersistedPeerState)>> {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT host, port, peer_type, weight, icp_port, max_connections,
+                        rtt_ms, hit_ewma, requests, hits, misses, errors, bytes_received, healthy
+                 FROM peer_state",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let peer_type_str: String = row.get(2)?;
+                let peer_type = peer_type_str.parse::<PeerType>().unwrap_or(PeerType::Parent);
+
+                let config = PeerConfig {
+                    host: row.get(0)?,
+                    port: row.get(1)?,
+                    peer_type,
+                    weight: row.get(3)?,
+                    icp_port: row.get(4)?,
+                    max_connections: row.get::<_, i64>(5)? as usize,
+                };
+                let state = PersistedPeerState {
+                    rtt_ms: row.get::<_, i64>(6)? as u64,
+                    hit_ewma: row.get(7)?,
+                    requests: row.get::<_, i64>(8)? as u64,
+                    hits: row.get::<_, i64>(9)? as u64,
+                    misses: row.get::<_, i64>(10)? as u64,
+                    errors: row.get::<_, i64>(11)? as u64,
+                    bytes_received: row.get::<_, i64>(12)? as u64,
+                    healthy: row.get::<_, i64>(13)? != 0,
+                };
+                Ok((config, state))
+            })?;
+            rows.collect()
+        })
+        .await;
+
+        match result {
+            Ok(Ok(peers)) => peers,
+            Ok(Err(e)) => {
+                error!("Failed to load persisted peers: {}", e);
+                Vec::new()
+            }
+            Err(e) => {
+                error!("Peer store load_all task panicked: {}", e);
+                Vec::new()
+            }
+        }
+    }
 }
 
 /// Manages all cache peers
 #[derive(Clone)]
 pub struct PeerRegistry {
-    peers: Arc<RwLock<HashMap<String, Arc<CachePeer>>>>,
+    /// Immutable snapshot of the peer map, published via `ArcSwap` instead
+    /// of guarded by a lock: writers (`add_peer`/`remove_peer`/
+    /// `load_from_store`) build a whole new `HashMap` and atomically swap it
+    /// in, while readers take a lock-free `load`/`load_full` of the current
+    /// one. Readers never block writers or each other, and never re-enter a
+    /// lock while chained through helpers like `healthy_peers_by_type`.
+    /// Wrapped in an outer `Arc` so cloning a `PeerRegistry` handle (as
+    /// `HierarchyManager::new` callers routinely do) shares the same live
+    /// map rather than forking an independent one.
+    peers: Arc<ArcSwap<HashMap<String, Arc<CachePeer>>>>,
+    store: Option<Arc<dyn PeerStore>>,
+    policy: SelectionPolicy,
 }
 
 impl PeerRegistry {
     pub fn new() -> Self {
         Self {
-            peers: Arc::new(RwLock::new(HashMap::new())),
+            peers: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            store: None,
+            policy: SelectionPolicy::WeightedScore,
+        }
+    }
+
+    /// Construct a registry backed by a persistence store. Peers added
+    /// afterward have their RTT/counters/health restored from it (see
+    /// `add_peer`), and `run_persistence_flusher` can be spawned to keep it
+    /// up to date in the background.
+    pub fn with_store(store: Arc<dyn PeerStore>) -> Self {
+        Self {
+            peers: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            store: Some(store),
+            policy: SelectionPolicy::WeightedScore,
+        }
+    }
+
+    /// Use `policy` instead of the default `WeightedScore` for
+    /// `select_parent`/`select_sibling`.
+    pub fn with_policy(mut self, policy: SelectionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Repopulate this registry from its persistence store, reconstructing
+    /// every peer a previous run persisted (config, RTT, counters, and
+    /// health all restored), so a restart doesn't re-learn peer quality from
+    /// scratch or hammer a peer it already knew was failing. A no-op if
+    /// this registry has no store, or the store has never been written to.
+    pub async fn load_from_store(&self) {
+        let Some(store) = self.store.clone() else { return };
+
+        let restored: Vec<Arc<CachePeer>> = store.load_all().await
+            .into_iter()
+            .map(|(config, state)| {
+                let peer = CachePeer::new(config);
+                peer.restore_state(&state);
+                Arc::new(peer)
+            })
+            .collect();
+
+        if restored.is_empty() {
+            return;
         }
+
+        self.peers.rcu(|current| {
+            let mut next = (**current).clone();
+            for peer in &restored {
+                next.insert(peer.id.clone(), peer.clone());
+            }
+            next
+        });
+    }
+
+    /// Construct a registry backed by a SQLite store at `db_path`, already
+    /// repopulated with every peer persisted by a previous run. Convenience
+    /// wrapper around `with_store` + `load_from_store` for the common case
+    /// of a proxy that keeps its entire peer list in the store rather than
+    /// re-adding peers from its own config on every boot.
+    #[cfg(feature = "peer-store-sqlite")]
+    pub async fn new_with_store(db_path: &str) -> rusqlite::Result<Self> {
+        let store = Arc::new(SqlitePeerStore::open(db_path).await?);
+        let registry = Self::with_store(store);
+        registry.load_from_store().await;
+        Ok(registry)
     }
 
-    /// Add a peer to the registry
+    /// Add a peer to the registry, restoring its RTT/counters/health from
+    /// the persistence store first if this registry has one and it holds a
+    /// matching entry.
     pub async fn add_peer(&self, config: PeerConfig) -> Arc<CachePeer> {
-        let peer = Arc::new(CachePeer::new(config));
+        let peer = CachePeer::new(config);
+
+        if let Some(store) = &self.store {
+            if let Some(state) = store.get(&peer.id).await {
+                peer.restore_state(&state);
+            }
+        }
+
+        let peer = Arc::new(peer);
         let id = peer.id.clone();
-        self.peers.write().await.insert(id, peer.clone());
+        self.peers.rcu(|current| {
+            let mut next = (**current).clone();
+            next.insert(id.clone(), peer.clone());
+            next
+        });
         peer
     }
 
     /// Remove a peer from the registry
     pub async fn remove_peer(&self, id: &str) -> bool {
-        self.peers.write().await.remove(id).is_some()
+        let mut removed = false;
+        self.peers.rcu(|current| {
+            let mut next = (**current).clone();
+            removed = next.remove(id).is_some();
+            next
+        });
+        removed
+    }
+
+    /// Lock-free, map-clone-free read of the current peer map. Selection
+    /// and health-check code should build directly on this instead of
+    /// chaining `all_peers()`/`healthy_peers()`, each of which would
+    /// otherwise re-derive its own view of the map.
+    pub fn snapshot(&self) -> Arc<HashMap<String, Arc<CachePeer>>> {
+        self.peers.load_full()
     }
 
     /// Get a peer by ID
     pub async fn get_peer(&self, id: &str) -> Option<Arc<CachePeer>> {
-        self.peers.read().await.get(id).cloned()
+        self.snapshot().get(id).cloned()
     }
 
     /// Get all peers
     pub async fn all_peers(&self) -> Vec<Arc<CachePeer>> {
-        self.peers.read().await.values().cloned().collect()
+        self.snapshot().values().cloned().collect()
     }
 
     /// Get all healthy peers
     pub async fn healthy_peers(&self) -> Vec<Arc<CachePeer>> {
-        self.all_peers().await
-            .into_iter()
+        self.snapshot()
+            .values()
             .filter(|p| p.is_healthy())
+            .cloned()
             .collect()
     }
 
     /// Get peers by type
     pub async fn peers_by_type(&self, peer_type: PeerType) -> Vec<Arc<CachePeer>> {
-        self.all_peers().await
-            .into_iter()
+        self.snapshot()
+            .values()
             .filter(|p| p.config.peer_type == peer_type)
+            .cloned()
             .collect()
     }
 
     /// Get healthy peers by type
     pub async fn healthy_peers_by_type(&self, peer_type: PeerType) -> Vec<Arc<CachePeer>> {
-        self.healthy_peers().await
-            .into_iter()
-            .filter(|p| p.config.peer_type == peer_type)
+        self.snapshot()
+            .values()
+            .filter(|p| p.is_healthy() && p.config.peer_type == peer_type)
+            .cloned()
             .collect()
     }
 
@@ -265,6 +1007,21 @@ impl PeerRegistry {
         self.healthy_peers_by_type(PeerType::Sibling).await
     }
 
+    /// Pick a parent to forward `key` (typically the request URL) to,
+    /// ranked by this registry's configured `SelectionPolicy`. Reads a
+    /// stable snapshot of the currently-healthy parents before ranking, so
+    /// it never returns an unhealthy peer.
+    pub async fn select_parent(&self, key: &str) -> Option<Arc<CachePeer>> {
+        let parents = self.parent_caches().await;
+        self.policy.strategy().select(&parents, key).cloned()
+    }
+
+    /// Sibling-cache counterpart to `select_parent`.
+    pub async fn select_sibling(&self, key: &str) -> Option<Arc<CachePeer>> {
+        let siblings = self.sibling_caches().await;
+        self.policy.strategy().select(&siblings, key).cloned()
+    }
+
     /// Check health of all peers and update status
     pub async fn health_check(&self) {
         let peers = self.all_peers().await;
@@ -283,6 +1040,18 @@ impl PeerRegistry {
         }
     }
 
+    /// Structured snapshots of every configured peer, regardless of health -
+    /// the "all peer data" half of a connected-vs-configured API.
+    pub async fn peer_snapshots(&self) -> Vec<PeerSnapshot> {
+        self.all_peers().await.iter().map(|p| p.snapshot()).collect()
+    }
+
+    /// Structured snapshots of only the currently-healthy/reachable peers -
+    /// the "connected peers" half of a connected-vs-configured API.
+    pub async fn connected_peer_snapshots(&self) -> Vec<PeerSnapshot> {
+        self.healthy_peers().await.iter().map(|p| p.snapshot()).collect()
+    }
+
     /// Get statistics summary
     pub async fn stats_summary(&self) -> String {
         let peers = self.all_peers().await;
@@ -296,9 +1065,10 @@ impl PeerRegistry {
             let errors = peer.stats.errors.load(Ordering::Relaxed);
             let hit_rate = peer.stats.hit_rate() * 100.0;
             let error_rate = peer.stats.error_rate() * 100.0;
+            let pool_exhausted = peer.stats.pool_exhausted.load(Ordering::Relaxed);
 
             summary.push_str(&format!(
-                "  {} [{}] healthy={} rtt={}ms score={:.2}\n    requests={} hits={} misses={} errors={} hit_rate={:.1}% error_rate={:.1}%\n",
+                "  {} [{}] healthy={} rtt={}ms score={:.2}\n    requests={} hits={} misses={} errors={} hit_rate={:.1}% error_rate={:.1}% pool_exhausted={}\n",
                 peer.id,
                 peer.config.peer_type,
                 peer.is_healthy(),
@@ -309,12 +1079,42 @@ impl PeerRegistry {
                 misses,
                 errors,
                 hit_rate,
-                error_rate
+                error_rate,
+                pool_exhausted
             ));
         }
 
         summary
     }
+
+    /// Periodically flush every peer's RTT EWMA, counters, and health flag
+    /// to the persistence store until `shutdown` is notified, flushing once
+    /// more before returning. A no-op if this registry has no store. The
+    /// peer list is read via a lock-free snapshot, so concurrent
+    /// `resolve_source` calls are never blocked on persistence.
+    pub async fn run_persistence_flusher(&self, interval: Duration, shutdown: Arc<Notify>) {
+        let Some(store) = self.store.clone() else { return };
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    self.flush_to_store(&store).await;
+                }
+                _ = shutdown.notified() => {
+                    self.flush_to_store(&store).await;
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn flush_to_store(&self, store: &Arc<dyn PeerStore>) {
+        let peers = self.all_peers().await;
+        for peer in peers {
+            store.put(&peer.id, &peer.config, peer.persisted_state()).await;
+        }
+    }
 }
 
 impl Default for PeerRegistry {
@@ -403,6 +1203,27 @@ mod tests {
         assert_eq!(siblings.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_cloned_registry_handles_share_the_same_peer_map() {
+        let registry = PeerRegistry::new();
+        let cloned = registry.clone();
+
+        registry.add_peer(PeerConfig {
+            host: "shared.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        }).await;
+
+        // A clone of the registry handle is a second view onto the same
+        // live map, not an independent fork - writes through one are
+        // visible through the other.
+        assert_eq!(cloned.all_peers().await.len(), 1);
+        assert_eq!(cloned.snapshot().len(), 1);
+    }
+
     #[test]
     fn test_peer_config_parse() {
         let result = PeerConfig::parse_from_string(
@@ -416,4 +1237,479 @@ mod tests {
         assert_eq!(config.port, 1488);
         assert_eq!(config.weight, 1.5);
     }
+
+    #[tokio::test]
+    async fn test_rtt_is_smoothed_not_overwritten() {
+        let config = PeerConfig {
+            host: "test.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        };
+        let peer = CachePeer::new(config);
+
+        peer.update_rtt(Duration::from_millis(100));
+        assert_eq!(peer.rtt(), Duration::from_millis(100));
+
+        // A single low sample should pull the EWMA down, not replace it.
+        peer.update_rtt(Duration::from_millis(10));
+        let smoothed = peer.rtt().as_millis();
+        assert!(smoothed > 10 && smoothed < 100, "expected a blended RTT, got {}ms", smoothed);
+    }
+
+    #[tokio::test]
+    async fn test_hit_ewma_rises_on_hit_and_decays_on_miss() {
+        let config = PeerConfig {
+            host: "test.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Sibling,
+            weight: 1.0,
+            icp_port: Some(3130),
+            max_connections: 100,
+        };
+        let peer = CachePeer::new(config);
+
+        assert_eq!(peer.hit_ewma(), INITIAL_HIT_EWMA);
+
+        peer.record_icp_hit();
+        peer.record_icp_hit();
+        peer.record_icp_hit();
+        assert!(peer.hit_ewma() > INITIAL_HIT_EWMA, "repeated hits should raise the EWMA");
+
+        let after_hits = peer.hit_ewma();
+        peer.record_icp_miss();
+        assert!(peer.hit_ewma() < after_hits, "a miss should pull the EWMA back down");
+    }
+
+    #[tokio::test]
+    async fn test_peer_snapshot_reflects_last_icp_outcome() {
+        let config = PeerConfig {
+            host: "test.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Sibling,
+            weight: 1.0,
+            icp_port: Some(3130),
+            max_connections: 100,
+        };
+        let peer = CachePeer::new(config);
+
+        assert_eq!(peer.snapshot().last_icp_outcome, None);
+
+        peer.record_icp_hit();
+        assert_eq!(peer.snapshot().last_icp_outcome, Some(IcpOutcome::Hit));
+
+        peer.record_icp_miss();
+        assert_eq!(peer.snapshot().last_icp_outcome, Some(IcpOutcome::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_registry_distinguishes_configured_from_connected_peers() {
+        let registry = PeerRegistry::new();
+
+        let healthy_config = PeerConfig {
+            host: "healthy.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        };
+        let unhealthy_config = PeerConfig {
+            host: "unhealthy.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        };
+
+        registry.add_peer(healthy_config).await;
+        let unhealthy = registry.add_peer(unhealthy_config).await;
+        unhealthy.set_healthy(false);
+
+        assert_eq!(registry.peer_snapshots().await.len(), 2);
+        assert_eq!(registry.connected_peer_snapshots().await.len(), 1);
+    }
+
+    /// In-memory `PeerStore` used only to exercise `PeerRegistry`'s
+    /// restore-on-add and background-flush behavior without a real backend.
+    #[derive(Default)]
+    struct MockPeerStore {
+        entries: RwLock<HashMap<String, (PeerConfig, PersistedPeerState)>>,
+    }
+
+    #[async_trait]
+    impl PeerStore for MockPeerStore {
+        async fn get(&self, peer_id: &str) -> Option<PersistedPeerState> {
+            self.entries.read().await.get(peer_id).map(|(_, state)| state.clone())
+        }
+
+        async fn put(&self, peer_id: &str, config: &PeerConfig, state: PersistedPeerState) {
+            self.entries.write().await.insert(peer_id.to_string(), (config.clone(), state));
+        }
+
+        async fn load_all(&self) -> Vec<(PeerConfig, PersistedPeerState)> {
+            self.entries.read().await.values().cloned().collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_peer_restores_persisted_state() {
+        let store = Arc::new(MockPeerStore::default());
+        let config = PeerConfig {
+            host: "test.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        };
+        let peer_id = format!("{}:{}:{}", PeerType::Parent, config.host, config.port);
+
+        store
+            .put(
+                &peer_id,
+                &config,
+                PersistedPeerState {
+                    rtt_ms: 42,
+                    hit_ewma: 0.8,
+                    requests: 10,
+                    hits: 7,
+                    misses: 3,
+                    errors: 1,
+                    bytes_received: 2048,
+                    healthy: false,
+                },
+            )
+            .await;
+
+        let registry = PeerRegistry::with_store(store);
+        let peer = registry.add_peer(config).await;
+
+        assert_eq!(peer.rtt(), Duration::from_millis(42));
+        assert_eq!(peer.hit_ewma(), 0.8);
+        assert_eq!(peer.stats.requests.load(Ordering::Relaxed), 10);
+        assert_eq!(peer.stats.hits.load(Ordering::Relaxed), 7);
+        assert_eq!(peer.stats.bytes_received.load(Ordering::Relaxed), 2048);
+        assert!(!peer.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_flush_to_store_persists_current_counters() {
+        let store = Arc::new(MockPeerStore::default());
+        let registry = PeerRegistry::with_store(store.clone());
+
+        let config = PeerConfig {
+            host: "test.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        };
+        let peer = registry.add_peer(config).await;
+        peer.stats.record_request().await;
+        peer.stats.record_hit(1024).await;
+
+        registry.flush_to_store(&(store.clone() as Arc<dyn PeerStore>)).await;
+
+        let persisted = store.get(&peer.id).await.expect("state should be persisted");
+        assert_eq!(persisted.requests, 1);
+        assert_eq!(persisted.hits, 1);
+        assert_eq!(persisted.bytes_received, 1024);
+    }
+
+    #[tokio::test]
+    async fn test_load_from_store_repopulates_peers_without_add_peer() {
+        let store = Arc::new(MockPeerStore::default());
+        let config = PeerConfig {
+            host: "parent.example.com".to_string(),
+            port: 3128,
+            peer_type: PeerType::Parent,
+            weight: 2.0,
+            icp_port: Some(3130),
+            max_connections: 50,
+        };
+        let peer_id = format!("{}:{}:{}", config.peer_type, config.host, config.port);
+
+        store
+            .put(
+                &peer_id,
+                &config,
+                PersistedPeerState {
+                    rtt_ms: 15,
+                    hit_ewma: 0.6,
+                    requests: 100,
+                    hits: 80,
+                    misses: 20,
+                    errors: 2,
+                    bytes_received: 4096,
+                    healthy: true,
+                },
+            )
+            .await;
+
+        // Unlike `test_add_peer_restores_persisted_state`, nothing calls
+        // `add_peer` here - `load_from_store` must reconstruct the peer's
+        // config from the store alone.
+        let registry = PeerRegistry::with_store(store);
+        registry.load_from_store().await;
+
+        let peer = registry.get_peer(&peer_id).await.expect("peer should be repopulated");
+        assert_eq!(peer.config.host, "parent.example.com");
+        assert_eq!(peer.config.icp_port, Some(3130));
+        assert_eq!(peer.config.max_connections, 50);
+        assert_eq!(peer.rtt(), Duration::from_millis(15));
+        assert_eq!(peer.stats.requests.load(Ordering::Relaxed), 100);
+        assert_eq!(peer.stats.bytes_received.load(Ordering::Relaxed), 4096);
+        assert!(peer.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_load_from_store_is_noop_without_store() {
+        let registry = PeerRegistry::new();
+        registry.load_from_store().await;
+        assert_eq!(registry.all_peers().await.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_select_parent_defaults_to_weighted_score_and_skips_unhealthy() {
+        let registry = PeerRegistry::new();
+
+        let fast = registry.add_peer(PeerConfig {
+            host: "fast.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 10.0,
+            icp_port: None,
+            max_connections: 100,
+        }).await;
+        fast.update_rtt(Duration::from_millis(5));
+
+        let unhealthy = registry.add_peer(PeerConfig {
+            host: "down.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1000.0,
+            icp_port: None,
+            max_connections: 100,
+        }).await;
+        unhealthy.set_healthy(false);
+
+        let selected = registry.select_parent("http://example.com/a").await;
+        assert_eq!(selected.unwrap().id, fast.id);
+    }
+
+    #[tokio::test]
+    async fn test_select_parent_with_lowest_rtt_policy() {
+        let registry = PeerRegistry::new().with_policy(crate::selection::SelectionPolicy::LowestRtt);
+
+        let slow = registry.add_peer(PeerConfig {
+            host: "slow.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        }).await;
+        slow.update_rtt(Duration::from_millis(200));
+
+        let fast = registry.add_peer(PeerConfig {
+            host: "fast.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        }).await;
+        fast.update_rtt(Duration::from_millis(5));
+
+        let selected = registry.select_parent("http://example.com/a").await;
+        assert_eq!(selected.unwrap().id, fast.id);
+    }
+
+    #[tokio::test]
+    async fn test_select_sibling_returns_none_without_siblings() {
+        let registry = PeerRegistry::new();
+        registry.add_peer(PeerConfig {
+            host: "parent.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        }).await;
+
+        assert!(registry.select_sibling("http://example.com/a").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_select_parent_with_rendezvous_policy_is_deterministic() {
+        let registry = PeerRegistry::new().with_policy(crate::selection::SelectionPolicy::Rendezvous);
+
+        for i in 0..4 {
+            registry.add_peer(PeerConfig {
+                host: format!("parent{}.example.com", i),
+                port: 1488,
+                peer_type: PeerType::Parent,
+                weight: 1.0,
+                icp_port: None,
+                max_connections: 100,
+            }).await;
+        }
+
+        let first = registry.select_parent("http://example.com/a").await.map(|p| p.id);
+        let second = registry.select_parent("http://example.com/a").await.map(|p| p.id);
+        assert_eq!(first, second);
+    }
+
+    fn test_peer() -> CachePeer {
+        CachePeer::new(PeerConfig {
+            host: "breaker.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections: 100,
+        })
+    }
+
+    #[test]
+    fn test_circuit_starts_closed_and_healthy() {
+        let peer = test_peer();
+        assert_eq!(peer.circuit_state(), CircuitState::Closed);
+        assert!(peer.is_healthy());
+    }
+
+    #[test]
+    fn test_record_probe_failure_trips_circuit_open_and_unhealthy() {
+        let peer = test_peer();
+        peer.record_probe_result(false);
+        assert_eq!(peer.circuit_state(), CircuitState::Open);
+        assert!(!peer.is_healthy());
+    }
+
+    #[test]
+    fn test_half_open_closes_after_success_threshold() {
+        let peer = test_peer().with_circuit_config(CircuitBreakerConfig {
+            open_duration: Duration::ZERO,
+            probe_interval: Duration::ZERO,
+            success_threshold: 2,
+        });
+
+        peer.trip_circuit();
+        assert!(peer.ready_for_probe());
+        assert_eq!(peer.circuit_state(), CircuitState::HalfOpen);
+        assert!(peer.is_healthy());
+
+        peer.record_probe_result(true);
+        assert_eq!(peer.circuit_state(), CircuitState::HalfOpen, "one success is below the threshold of 2");
+
+        peer.record_probe_result(true);
+        assert_eq!(peer.circuit_state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_failure_reopens_circuit() {
+        let peer = test_peer().with_circuit_config(CircuitBreakerConfig {
+            open_duration: Duration::ZERO,
+            probe_interval: Duration::ZERO,
+            success_threshold: 2,
+        });
+
+        peer.trip_circuit();
+        assert!(peer.ready_for_probe());
+        assert_eq!(peer.circuit_state(), CircuitState::HalfOpen);
+
+        peer.record_probe_result(false);
+        assert_eq!(peer.circuit_state(), CircuitState::Open);
+        assert!(!peer.is_healthy());
+    }
+
+    #[test]
+    fn test_ready_for_probe_respects_open_duration_cooldown() {
+        let peer = test_peer().with_circuit_config(CircuitBreakerConfig {
+            open_duration: Duration::from_secs(3600),
+            probe_interval: Duration::ZERO,
+            success_threshold: 1,
+        });
+
+        peer.trip_circuit();
+        // Cool-down hasn't elapsed yet, so the prober shouldn't probe this
+        // peer out of turn and the circuit must stay Open.
+        assert!(!peer.ready_for_probe());
+        assert_eq!(peer.circuit_state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_ready_for_probe_respects_probe_interval() {
+        let peer = test_peer().with_circuit_config(CircuitBreakerConfig {
+            open_duration: Duration::ZERO,
+            probe_interval: Duration::from_secs(3600),
+            success_threshold: 1,
+        });
+
+        assert!(peer.ready_for_probe());
+        // Probed once already; the next check is well within the interval.
+        assert!(!peer.ready_for_probe());
+    }
+
+    #[test]
+    fn test_set_healthy_false_trips_and_true_closes_circuit() {
+        let peer = test_peer();
+        peer.set_healthy(false);
+        assert_eq!(peer.circuit_state(), CircuitState::Open);
+
+        peer.set_healthy(true);
+        assert_eq!(peer.circuit_state(), CircuitState::Closed);
+        assert!(peer.is_healthy());
+    }
+
+    fn peer_with_max_connections(max_connections: usize) -> CachePeer {
+        CachePeer::new(PeerConfig {
+            host: "pool.example.com".to_string(),
+            port: 1488,
+            peer_type: PeerType::Parent,
+            weight: 1.0,
+            icp_port: None,
+            max_connections,
+        })
+    }
+
+    #[test]
+    fn test_try_acquire_connection_respects_max_connections() {
+        let peer = peer_with_max_connections(2);
+
+        let first = peer.try_acquire_connection();
+        let second = peer.try_acquire_connection();
+        assert!(first.is_some());
+        assert!(second.is_some());
+
+        // Pool is now at its ceiling of 2.
+        assert!(peer.try_acquire_connection().is_none());
+        assert_eq!(peer.stats.pool_exhausted.load(Ordering::Relaxed), 1);
+
+        drop(first);
+        assert!(peer.try_acquire_connection().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_connection_waits_for_a_released_slot() {
+        let peer = Arc::new(peer_with_max_connections(1));
+        let held = peer.try_acquire_connection().expect("pool starts empty");
+
+        let waiter_peer = peer.clone();
+        let waiter = tokio::spawn(async move {
+            waiter_peer.acquire_connection().await;
+        });
+
+        // Give the waiter a chance to block on the exhausted pool.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        waiter.await.expect("waiter task panicked");
+    }
 }
@@ -128,6 +128,64 @@ impl SelectionStrategy for ClosestStrategy {
     }
 }
 
+/// Gossip-weighted selection - like `WeightedStrategy`, but biases toward
+/// peers recently observed via ICP to actually hold content rather than
+/// just their static config weight. Score is
+/// `config.weight * hit_ewma / (1 + rtt_ms)`, normalized across the
+/// candidate set for a weighted-random draw.
+pub struct GossipWeightedStrategy;
+
+impl GossipWeightedStrategy {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn gossip_score(peer: &CachePeer) -> f64 {
+        if !peer.is_healthy() {
+            return 0.0;
+        }
+
+        let rtt_ms = peer.rtt().as_millis() as f64;
+        peer.config.weight * peer.hit_ewma() / (1.0 + rtt_ms)
+    }
+}
+
+impl Default for GossipWeightedStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectionStrategy for GossipWeightedStrategy {
+    fn select<'a>(&self, peers: &'a [Arc<CachePeer>], _url: &str) -> Option<&'a Arc<CachePeer>> {
+        if peers.is_empty() {
+            return None;
+        }
+
+        let total_score: f64 = peers.iter().map(|p| Self::gossip_score(p)).sum();
+
+        if total_score == 0.0 {
+            return None; // All peers unhealthy or never seen a hit
+        }
+
+        let mut rng = rand::random::<f64>() * total_score;
+
+        for peer in peers {
+            let score = Self::gossip_score(peer);
+            if rng <= score {
+                return Some(peer);
+            }
+            rng -= score;
+        }
+
+        peers.last()
+    }
+
+    fn name(&self) -> &'static str {
+        "gossip-weighted"
+    }
+}
+
 /// Hash-based selection - consistent hashing by URL
 pub struct HashStrategy;
 
@@ -165,13 +223,155 @@ impl SelectionStrategy for HashStrategy {
     }
 }
 
+/// Rendezvous (highest-random-weight) hashing. Unlike `HashStrategy`'s
+/// `hash(url) % peers.len()`, adding or removing a peer here only remaps
+/// the keys that peer itself would have owned - every other URL keeps
+/// mapping to the same peer, preserving cache locality across membership
+/// changes. For each healthy peer, scores `hash64(url, peer_id)` via the
+/// weighted-HRW transform `-weight / ln(h / 2^64)` and picks the max,
+/// ties broken by peer id.
+pub struct RendezvousStrategy;
+
+impl RendezvousStrategy {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn hash64(url: &str, peer_id: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        peer_id.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn score(url: &str, peer: &CachePeer) -> f64 {
+        let hash = Self::hash64(url, &peer.id);
+        // Normalize into (0, 1), never exactly 0, so ln() stays finite.
+        let normalized = (hash as f64 + 1.0) / (u64::MAX as f64 + 2.0);
+        -peer.config.weight / normalized.ln()
+    }
+}
+
+impl Default for RendezvousStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectionStrategy for RendezvousStrategy {
+    fn select<'a>(&self, peers: &'a [Arc<CachePeer>], url: &str) -> Option<&'a Arc<CachePeer>> {
+        peers
+            .iter()
+            .filter(|p| p.is_healthy())
+            .max_by(|a, b| {
+                Self::score(url, a)
+                    .partial_cmp(&Self::score(url, b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| a.id.cmp(&b.id))
+            })
+    }
+
+    fn name(&self) -> &'static str {
+        "rendezvous"
+    }
+}
+
+/// CARP (Cache Array Routing Protocol): consistent weighted hashing of the
+/// request key across peers via the same `combined_hash(hash(key),
+/// hash(identity)) * weight` scoring `icp.rs`'s `IcpClient::rank_parents`
+/// uses for ICP parent selection, so plain HTTP parent selection and ICP
+/// parent selection agree on which peer owns a given key when both score
+/// the same peer identity strings.
+pub struct CarpStrategy;
+
+impl CarpStrategy {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for CarpStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectionStrategy for CarpStrategy {
+    fn select<'a>(&self, peers: &'a [Arc<CachePeer>], url: &str) -> Option<&'a Arc<CachePeer>> {
+        peers
+            .iter()
+            .filter(|p| p.is_healthy())
+            .max_by(|a, b| {
+                crate::icp::carp_score(url, &a.id, a.config.weight)
+                    .partial_cmp(&crate::icp::carp_score(url, &b.id, b.config.weight))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| a.id.cmp(&b.id))
+            })
+    }
+
+    fn name(&self) -> &'static str {
+        "carp"
+    }
+}
+
+/// Power-of-two-choices selection: sample two distinct healthy candidates
+/// at random and return the higher-scoring one. Spreads load across every
+/// near-equally-good peer instead of `WeightedStrategy`'s single global
+/// draw, which can still herd traffic onto whichever peer is transiently
+/// scored highest.
+pub struct PowerOfTwoChoicesStrategy;
+
+impl PowerOfTwoChoicesStrategy {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for PowerOfTwoChoicesStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectionStrategy for PowerOfTwoChoicesStrategy {
+    fn select<'a>(&self, peers: &'a [Arc<CachePeer>], _url: &str) -> Option<&'a Arc<CachePeer>> {
+        let healthy: Vec<&'a Arc<CachePeer>> = peers.iter().filter(|p| p.is_healthy()).collect();
+
+        match healthy.len() {
+            0 => None,
+            1 => Some(healthy[0]),
+            n => {
+                let i = (rand::random::<f64>() * n as f64) as usize % n;
+                let mut j = (rand::random::<f64>() * n as f64) as usize % n;
+                if j == i {
+                    j = (j + 1) % n;
+                }
+
+                if healthy[i].score() >= healthy[j].score() {
+                    Some(healthy[i])
+                } else {
+                    Some(healthy[j])
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "power-of-two-choices"
+    }
+}
+
 /// Parse strategy from string
 pub fn parse_strategy(name: &str) -> Box<dyn SelectionStrategy> {
     match name.to_lowercase().as_str() {
         "round-robin" | "roundrobin" | "rr" => Box::new(RoundRobinStrategy::new()),
         "weighted" | "weight" | "w" => Box::new(WeightedStrategy::new()),
+        "gossip-weighted" | "gossip" | "gw" => Box::new(GossipWeightedStrategy::new()),
         "closest" | "rtt" | "latency" => Box::new(ClosestStrategy::new()),
         "hash" | "consistent" | "ch" => Box::new(HashStrategy::new()),
+        "rendezvous" | "hrw" => Box::new(RendezvousStrategy::new()),
+        "carp" => Box::new(CarpStrategy::new()),
+        "power-of-two-choices" | "power-of-two" | "p2c" => Box::new(PowerOfTwoChoicesStrategy::new()),
         _ => {
             tracing::warn!("Unknown strategy '{}', defaulting to weighted", name);
             Box::new(WeightedStrategy::new())
@@ -179,6 +379,42 @@ pub fn parse_strategy(name: &str) -> Box<dyn SelectionStrategy> {
     }
 }
 
+/// Named selection policy for `PeerRegistry::select_parent`/`select_sibling`,
+/// each backed by one of this module's `SelectionStrategy` implementations.
+/// Unlike `parse_strategy` (a free-form string used to configure the
+/// hierarchy's parent-selection strategy), this is the closed set a peer
+/// registry offers for ranking candidates by a request key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionPolicy {
+    /// `CachePeer::score()` - weight, error rate, and RTT combined.
+    WeightedScore,
+    /// The healthy peer with the lowest RTT EWMA.
+    LowestRtt,
+    /// Sample two random healthy candidates, return the higher-scoring one.
+    PowerOfTwoChoices,
+    /// Rendezvous (highest-random-weight) hashing of the request key across
+    /// peers, so the same key always maps to the same peer regardless of
+    /// request order. Note this is HRW hashing, not the CARP algorithm
+    /// below, despite the similar goal.
+    Rendezvous,
+    /// Consistent weighted hashing of the request key across peers via the
+    /// actual CARP algorithm (`icp.rs`'s `combined_hash`/`rank_parents`).
+    Carp,
+}
+
+impl SelectionPolicy {
+    /// The `SelectionStrategy` implementation backing this policy.
+    pub fn strategy(&self) -> Box<dyn SelectionStrategy> {
+        match self {
+            SelectionPolicy::WeightedScore => Box::new(WeightedStrategy::new()),
+            SelectionPolicy::LowestRtt => Box::new(ClosestStrategy::new()),
+            SelectionPolicy::PowerOfTwoChoices => Box::new(PowerOfTwoChoicesStrategy::new()),
+            SelectionPolicy::Rendezvous => Box::new(RendezvousStrategy::new()),
+            SelectionPolicy::Carp => Box::new(CarpStrategy::new()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -278,8 +514,154 @@ mod tests {
     fn test_parse_strategy() {
         assert_eq!(parse_strategy("round-robin").name(), "round-robin");
         assert_eq!(parse_strategy("weighted").name(), "weighted");
+        assert_eq!(parse_strategy("gossip-weighted").name(), "gossip-weighted");
         assert_eq!(parse_strategy("closest").name(), "closest");
         assert_eq!(parse_strategy("hash").name(), "hash");
+        assert_eq!(parse_strategy("rendezvous").name(), "rendezvous");
+        assert_eq!(parse_strategy("hrw").name(), "rendezvous");
         assert_eq!(parse_strategy("unknown").name(), "weighted"); // Default
     }
+
+    #[test]
+    fn test_rendezvous_hash_consistency() {
+        let strategy = RendezvousStrategy::new();
+        let peers = vec![
+            create_test_peer("peer1", 1.0, 10),
+            create_test_peer("peer2", 1.0, 20),
+            create_test_peer("peer3", 1.0, 30),
+        ];
+
+        let url = "http://example.com/test";
+        let selected1 = strategy.select(&peers, url);
+        let selected2 = strategy.select(&peers, url);
+
+        assert_eq!(selected1.unwrap().id, selected2.unwrap().id);
+    }
+
+    #[test]
+    fn test_rendezvous_minimal_remap_on_peer_removal() {
+        let strategy = RendezvousStrategy::new();
+        let full_set = vec![
+            create_test_peer("peer1", 1.0, 10),
+            create_test_peer("peer2", 1.0, 20),
+            create_test_peer("peer3", 1.0, 30),
+            create_test_peer("peer4", 1.0, 40),
+            create_test_peer("peer5", 1.0, 50),
+        ];
+
+        let urls: Vec<String> = (0..200).map(|i| format!("http://example.com/{}", i)).collect();
+        let before: Vec<String> = urls
+            .iter()
+            .map(|u| strategy.select(&full_set, u).unwrap().id.clone())
+            .collect();
+
+        // Remove peer3; every key that wasn't owned by peer3 should still
+        // map to the same peer it did before.
+        let reduced_set: Vec<_> = full_set
+            .iter()
+            .filter(|p| p.config.host != "peer3")
+            .cloned()
+            .collect();
+
+        let mut unchanged = 0;
+        let mut total_not_owned_by_removed = 0;
+        for (url, previous_id) in urls.iter().zip(before.iter()) {
+            if previous_id.contains("peer3") {
+                continue;
+            }
+            total_not_owned_by_removed += 1;
+            let after = strategy.select(&reduced_set, url).unwrap();
+            if &after.id == previous_id {
+                unchanged += 1;
+            }
+        }
+
+        assert_eq!(
+            unchanged, total_not_owned_by_removed,
+            "removing one peer should not remap keys owned by other peers"
+        );
+    }
+
+    #[test]
+    fn test_gossip_weighted_favors_recently_confirmed_peer() {
+        let strategy = GossipWeightedStrategy::new();
+        let peers = vec![
+            create_test_peer("peer1", 1.0, 10),
+            create_test_peer("peer2", 1.0, 10),
+        ];
+
+        // peer1 has never been confirmed to hold content; peer2 has been
+        // repeatedly confirmed via ICP HITs even though config weight and
+        // RTT are identical.
+        for _ in 0..10 {
+            peers[1].record_icp_hit();
+        }
+        for _ in 0..10 {
+            peers[0].record_icp_miss();
+        }
+
+        let mut counts = std::collections::HashMap::new();
+        for i in 0..100 {
+            let url = format!("http://example.com/{}", i);
+            if let Some(peer) = strategy.select(&peers, &url) {
+                *counts.entry(peer.config.host.clone()).or_insert(0) += 1;
+            }
+        }
+
+        assert!(counts.get("peer2").unwrap_or(&0) > counts.get("peer1").unwrap_or(&0));
+    }
+
+    #[test]
+    fn test_gossip_weighted_empty_peers() {
+        let strategy = GossipWeightedStrategy::new();
+        let peers: Vec<Arc<CachePeer>> = vec![];
+        assert!(strategy.select(&peers, "http://example.com/test").is_none());
+    }
+
+    #[test]
+    fn test_power_of_two_choices_only_picks_healthy_peers() {
+        let strategy = PowerOfTwoChoicesStrategy::new();
+        let peers = vec![
+            create_test_peer("peer1", 1.0, 10),
+            create_test_peer("peer2", 1.0, 20),
+            create_test_peer("peer3", 1.0, 30),
+        ];
+        peers[1].set_healthy(false);
+
+        for i in 0..50 {
+            let url = format!("http://example.com/{}", i);
+            let selected = strategy.select(&peers, &url).unwrap();
+            assert_ne!(selected.config.host, "peer2");
+        }
+    }
+
+    #[test]
+    fn test_power_of_two_choices_single_candidate() {
+        let strategy = PowerOfTwoChoicesStrategy::new();
+        let peers = vec![create_test_peer("peer1", 1.0, 10)];
+        let selected = strategy.select(&peers, "http://example.com/test");
+        assert_eq!(selected.unwrap().config.host, "peer1");
+    }
+
+    #[test]
+    fn test_power_of_two_choices_empty_peers() {
+        let strategy = PowerOfTwoChoicesStrategy::new();
+        let peers: Vec<Arc<CachePeer>> = vec![];
+        assert!(strategy.select(&peers, "http://example.com/test").is_none());
+    }
+
+    #[test]
+    fn test_parse_strategy_resolves_power_of_two_choices() {
+        let strategy = parse_strategy("p2c");
+        assert_eq!(strategy.name(), "power-of-two-choices");
+    }
+
+    #[test]
+    fn test_selection_policy_maps_to_matching_strategy_name() {
+        assert_eq!(SelectionPolicy::WeightedScore.strategy().name(), "weighted");
+        assert_eq!(SelectionPolicy::LowestRtt.strategy().name(), "closest");
+        assert_eq!(SelectionPolicy::PowerOfTwoChoices.strategy().name(), "power-of-two-choices");
+        assert_eq!(SelectionPolicy::Rendezvous.strategy().name(), "rendezvous");
+        assert_eq!(SelectionPolicy::Carp.strategy().name(), "carp");
+    }
 }